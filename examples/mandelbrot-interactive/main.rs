@@ -6,12 +6,13 @@ use tufa::{
         egui::{self, Context, DragValue, Slider},
         encase::ShaderType,
         nalgebra::Vector2,
-        wgpu::{include_wgsl, RenderPass, ShaderStages},
+        wgpu::{RenderPass, ShaderStages},
         winit::{dpi::LogicalSize, window::WindowAttributes},
     },
     gpu::Gpu,
     interactive::{GraphicsCtx, Interactive},
     pipeline::render::RenderPipeline,
+    shader::Preprocessor,
 };
 
 #[derive(ShaderType, Default)]
@@ -90,8 +91,9 @@ fn main() -> Result<()> {
     let gpu = Gpu::new()?;
 
     let uniform = gpu.create_uniform(&Uniform::default());
+    let shader = Preprocessor::new().process("render.wgsl", include_str!("render.wgsl"))?;
     let render = gpu
-        .render_pipeline(include_wgsl!("render.wgsl"))
+        .render_pipeline(&shader)
         .bind(&uniform, ShaderStages::FRAGMENT)
         .finish();
 
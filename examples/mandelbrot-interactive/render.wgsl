@@ -1,15 +1,5 @@
-struct VertexOutput {
-    @builtin(position) pos: vec4<f32>,
-    @location(1) uv: vec2<f32>,
-};
-
-@vertex
-fn vert(
-    @location(0) pos: vec4<f32>,
-    @location(1) uv: vec2<f32>,
-) -> VertexOutput {
-    return VertexOutput(pos, uv);
-}
+#include "tufa/quad.wgsl"
+#include "tufa/color.wgsl"
 
 @group(0) @binding(0) var<uniform> ctx: Uniform;
 
@@ -56,12 +46,3 @@ fn cPow(z: vec2<f32>, n: u32) -> vec2<f32> {
     var arg = atan2(z.y, z.x) * f32(n);
     return pow(length(z), f32(n)) * vec2(cos(arg), sin(arg));
 }
-
-fn hueShift(color: vec3<f32>, hue: f32) -> vec3<f32> {
-    var k = vec3(0.57735, 0.57735, 0.57735);
-    var cosAngle = cos(hue);
-    return vec3(color * cosAngle
-                + cross(k, color) * sin(hue)
-                + k * dot(k, color) * (1.0 - cosAngle)
-    );
-}
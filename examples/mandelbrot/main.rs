@@ -1,3 +1,5 @@
+use std::thread;
+
 use anyhow::Result;
 
 use image::{ImageBuffer, Rgb};
@@ -30,25 +32,33 @@ fn main() -> Result<()> {
 
     let mut pipeline = gpu
         .compute_pipeline(include_wgsl!("shader.wgsl"))
+        .constant("N", 1000)
         .bind(&uniform)
         .bind(&buffer)
         .finish();
 
-    for zoom in 0..10_0 {
+    let mut saves = Vec::new();
+    for zoom in 0..100 {
         uniform.upload(&Uniform {
             size: SIZE,
             zoom: zoom as f32 / 10.0,
         });
 
-        pipeline.dispatch(Vector3::new(SIZE.x / 8, SIZE.y / 8, 1));
-        buffer.download_async(move |result| {
+        pipeline.dispatch_threads(Vector3::new(SIZE.x, SIZE.y, 1));
+        let result = buffer.download_async();
+        saves.push(thread::spawn(move || {
+            let result = pollster::block_on(result);
             ImageBuffer::from_par_fn(SIZE.x, SIZE.y, |x, y| {
                 let color = result[(y * SIZE.x + x) as usize];
                 Rgb([color as u8, (color >> 8) as u8, (color >> 16) as u8])
             })
             .save(format!("rec/out-{zoom:0>4}.png"))
             .unwrap();
-        });
+        }));
+    }
+
+    for save in saves {
+        save.join().unwrap();
     }
 
     Ok(())
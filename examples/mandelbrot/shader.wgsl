@@ -8,12 +8,16 @@ struct Uniform {
 
 const PI: f32 = 3.1415926538;
 
-const N: i32 = 1000;
+override N: i32 = 1000;
 const POI: vec2<f32> = vec2(-1.7864323556423187, -2.905726428359401e-7);
 
 @compute
 @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) pos: vec3<u32>) {
+    if any(pos.xy >= ctx.size) {
+        return;
+    }
+
     var zoom = 4.0 / exp(ctx.zoom);
     var c = (vec2(f32(pos.x), f32(pos.y)) / f32(ctx.size.x) - 0.5) * zoom + POI;
 
@@ -1,9 +1,12 @@
-use std::{f32::consts::TAU, time::Instant};
+use std::{
+    f32::consts::TAU,
+    thread::{self, JoinHandle},
+    time::Instant,
+};
 
 use anyhow::{Ok, Result};
 use image::{ImageBuffer, Rgb};
 use tufa::{
-    bindings::buffer::mutability::Immutable,
     export::{
         nalgebra::{Matrix4, Vector2, Vector3, Vector4},
         wgpu::{include_wgsl, ShaderStages},
@@ -33,10 +36,11 @@ fn main() -> Result<()> {
         .finish();
 
     let (texture, depth) = (gpu.create_texture_2d(SIZE), gpu.create_texture_2d(SIZE));
-    let staging = gpu.create_storage_empty::<_, Immutable>(4 * (SIZE.x * SIZE.y) as u64);
+    let mut readback = gpu.create_readback_ring::<Vec<u32>>(3);
 
     let start = Instant::now();
     let aspect = SIZE.x as f32 / SIZE.y as f32;
+    let mut saves = Vec::new();
     for i in 0..FRAMES {
         let t = i as f32 / FRAMES as f32;
         let rotation = Matrix4::new_rotation(Vector3::y() * TAU * t);
@@ -46,19 +50,32 @@ fn main() -> Result<()> {
             render.draw(render_pass, &index, &vertex, 0..3)
         });
 
-        texture.copy_to_buffer(&staging);
-        staging.download_async(move |result: Vec<u32>| {
-            ImageBuffer::from_par_fn(SIZE.x, SIZE.y, |x, y| {
-                let color = result[(y * SIZE.x + x) as usize];
-                Rgb([color as u8, (color >> 8) as u8, (color >> 16) as u8])
-            })
-            .save(format!("out/{i:0>3}-out.png"))
-            .unwrap();
-        });
+        if let Some(frame) = readback.push_texture(&texture) {
+            saves.push(save(saves.len(), frame));
+        }
+    }
+
+    while let Some(frame) = readback.pop() {
+        saves.push(save(saves.len(), frame));
+    }
+
+    for save in saves {
+        save.join().unwrap();
     }
 
     let elapsed = start.elapsed();
-    println!("FPS: {:.2}", FRAMES as f32 / elapsed.as_secs_f32() as f32);
+    println!("FPS: {:.2}", FRAMES as f32 / elapsed.as_secs_f32());
 
     Ok(())
 }
+
+fn save(i: usize, frame: Vec<u32>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        ImageBuffer::from_par_fn(SIZE.x, SIZE.y, |x, y| {
+            let color = frame[(y * SIZE.x + x) as usize];
+            Rgb([color as u8, (color >> 8) as u8, (color >> 16) as u8])
+        })
+        .save(format!("out/{i:0>3}-out.png"))
+        .unwrap();
+    })
+}
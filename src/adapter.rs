@@ -0,0 +1,118 @@
+//! Information on the adapters (GPUs) available to tufa.
+//!
+//! Use [`Gpu::enumerate_adapters`] to check what the system supports before building a [`Gpu`].
+
+use std::fmt::{self, Display};
+
+use wgpu::{Adapter, Backend, DeviceType, Features, Limits};
+
+use crate::gpu::Gpu;
+
+/// Describes an adapter and its capabilities.
+/// Its [`Display`] implementation writes a human readable capability report.
+#[derive(Debug, Clone)]
+pub struct AdapterDescription {
+    pub name: String,
+    pub backend: Backend,
+    pub device_type: DeviceType,
+    pub driver: String,
+    pub driver_info: String,
+
+    pub features: Features,
+    pub limits: Limits,
+}
+
+impl AdapterDescription {
+    pub(crate) fn new(adapter: &Adapter) -> Self {
+        let info = adapter.get_info();
+        Self {
+            name: info.name,
+            backend: info.backend,
+            device_type: info.device_type,
+            driver: info.driver,
+            driver_info: info.driver_info,
+
+            features: adapter.features(),
+            limits: adapter.limits(),
+        }
+    }
+
+    /// Checks if the adapter supports all of the given features.
+    pub fn supports(&self, features: Features) -> bool {
+        self.features.contains(features)
+    }
+
+    /// Checks if the adapter supports the features enabled by [`crate::gpu::GpuBuilder::with_raytracing`].
+    pub fn supports_raytracing(&self) -> bool {
+        self.supports(
+            Features::EXPERIMENTAL_RAY_TRACING_ACCELERATION_STRUCTURE
+                | Features::EXPERIMENTAL_RAY_QUERY,
+        )
+    }
+
+    /// Returns the name of every limit in `limits` that exceeds what the adapter allows.
+    pub fn unsupported_limits(&self, limits: &Limits) -> Vec<&'static str> {
+        let mut out = Vec::new();
+        limits.check_limits_with_fail_fn(&self.limits, false, |name, _, _| out.push(name));
+        out
+    }
+}
+
+impl Display for AdapterDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limits = &self.limits;
+        let yes_no = |x: bool| if x { "yes" } else { "no" };
+
+        writeln!(f, "{}", self.name)?;
+        writeln!(f, "  Backend: {:?}", self.backend)?;
+        writeln!(f, "  Device type: {:?}", self.device_type)?;
+        let driver = format!("{} {}", self.driver, self.driver_info);
+        if !driver.trim().is_empty() {
+            writeln!(f, "  Driver: {}", driver.trim())?;
+        }
+        writeln!(f, "  Raytracing: {}", yes_no(self.supports_raytracing()))?;
+        writeln!(
+            f,
+            "  Push constants: {}",
+            yes_no(self.supports(Features::PUSH_CONSTANTS))
+        )?;
+        writeln!(f, "  Max buffer size: {} bytes", limits.max_buffer_size)?;
+        writeln!(
+            f,
+            "  Max storage buffer binding: {} bytes",
+            limits.max_storage_buffer_binding_size
+        )?;
+        writeln!(
+            f,
+            "  Max uniform buffer binding: {} bytes",
+            limits.max_uniform_buffer_binding_size
+        )?;
+        writeln!(
+            f,
+            "  Max texture size: {0}×{0}",
+            limits.max_texture_dimension_2d
+        )?;
+        writeln!(
+            f,
+            "  Max workgroup size: {}×{}×{} ({} invocations)",
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_workgroup_size_y,
+            limits.max_compute_workgroup_size_z,
+            limits.max_compute_invocations_per_workgroup
+        )?;
+        writeln!(
+            f,
+            "  Max workgroups per dimension: {}",
+            limits.max_compute_workgroups_per_dimension
+        )?;
+        writeln!(f, "  Max bind groups: {}", limits.max_bind_groups)?;
+        write!(f, "  Features: {:?}", self.features)
+    }
+}
+
+impl Gpu {
+    /// Lists all adapters available on the system, in the order used by [`crate::gpu::GpuBuilder::adapter_index`].
+    pub fn enumerate_adapters() -> Vec<AdapterDescription> {
+        Self::builder().adapters()
+    }
+}
@@ -0,0 +1,199 @@
+//! Recording many commands into a single submission.
+
+use std::ops::Range;
+
+use encase::{internal::WriteInto, ShaderType, StorageBuffer};
+use nalgebra::Vector3;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BufferUsages, CommandEncoder, CommandEncoderDescriptor, RenderPass,
+};
+
+use crate::{
+    bindings::{
+        buffer::{
+            ops::{encode_clear, encode_copy, encode_fill},
+            BufferBinding, BufferOps, DispatchIndirectArgs, DynamicOffset, IndirectBuffer,
+        },
+        texture::{
+            format::{Depth, Rgba8},
+            Texture,
+        },
+    },
+    gpu::Gpu,
+    pipeline::compute::{ComputePipeline, Workgroups},
+    submission::Submission,
+};
+
+/// Commands being recorded with [`Gpu::record`]. They run in the order they
+/// are recorded, once the whole batch is submitted.
+pub struct Batch<'a> {
+    gpu: &'a Gpu,
+    encoder: CommandEncoder,
+}
+
+impl Batch<'_> {
+    /// Records a dispatch of the pipeline on the specified number of workgroups.
+    pub fn dispatch(&mut self, pipeline: &mut ComputePipeline, workgroups: Vector3<u32>) {
+        pipeline.encode(&mut self.encoder, Workgroups::Direct(workgroups), &[], &[]);
+    }
+
+    /// Records a dispatch of the pipeline, pushing `data` to the shader's `var<push_constant>` first.
+    pub fn dispatch_push<T: ShaderType + WriteInto>(
+        &mut self,
+        pipeline: &mut ComputePipeline,
+        workgroups: Vector3<u32>,
+        data: &T,
+    ) {
+        let push = pipeline.push_bytes(data);
+        pipeline.encode(
+            &mut self.encoder,
+            Workgroups::Direct(workgroups),
+            &push,
+            &[],
+        );
+    }
+
+    /// Records a dispatch of the pipeline with the given elements of its dynamic
+    /// uniform buffers bound.
+    pub fn dispatch_dynamic(
+        &mut self,
+        pipeline: &mut ComputePipeline,
+        workgroups: Vector3<u32>,
+        offsets: &[DynamicOffset],
+    ) {
+        pipeline.encode(
+            &mut self.encoder,
+            Workgroups::Direct(workgroups),
+            &[],
+            offsets,
+        );
+    }
+
+    /// Records a dispatch of the pipeline with the number of workgroups stored
+    /// in the given entry of the indirect buffer.
+    pub fn dispatch_indirect(
+        &mut self,
+        pipeline: &mut ComputePipeline,
+        indirect: &IndirectBuffer<DispatchIndirectArgs>,
+        entry: u32,
+    ) {
+        let offset = indirect.offset(entry..entry + 1);
+        let buffer = indirect.buffer();
+        let workgroups = Workgroups::Indirect(&buffer, offset);
+        pipeline.encode(&mut self.encoder, workgroups, &[], &[]);
+    }
+
+    /// Records a render pass that clears and draws into the textures.
+    pub fn render_pass(
+        &mut self,
+        texture: &Texture<Rgba8>,
+        depth: &Texture<Depth>,
+        callback: impl FnOnce(&mut RenderPass),
+    ) {
+        self.gpu
+            .encode_render_pass(&mut self.encoder, texture, depth, callback);
+    }
+
+    /// Records a copy of the whole source buffer to the start of the destination.
+    /// Panics if the source is larger than the destination.
+    pub fn copy_buffer(&mut self, source: &impl BufferBinding, destination: &impl BufferBinding) {
+        let [source, destination] = self
+            .gpu
+            .binding_manager
+            .get_buffers([source.get_id(), destination.get_id()]);
+        assert!(
+            source.size() <= destination.size(),
+            "Can't copy a buffer of {} bytes into one of {} bytes",
+            source.size(),
+            destination.size()
+        );
+
+        self.encoder
+            .copy_buffer_to_buffer(&source, 0, &destination, 0, source.size());
+    }
+
+    /// Records a clear of the buffer. See [`BufferOps::clear`].
+    pub fn clear(&mut self, buffer: &impl BufferOps) {
+        encode_clear(&mut self.encoder, buffer);
+    }
+
+    /// Records a fill of the buffer with the value. See [`BufferOps::fill`].
+    pub fn fill<B: BufferOps>(&mut self, buffer: &B, value: &B::Element) {
+        encode_fill(&mut self.encoder, buffer, value);
+    }
+
+    /// Records a copy of the elements in `range` of the source buffer into the
+    /// destination, starting at element `offset`. See [`BufferOps::copy_from`].
+    pub fn copy_range<B: BufferOps>(
+        &mut self,
+        source: &impl BufferOps<Element = B::Element>,
+        range: Range<usize>,
+        destination: &B,
+        offset: usize,
+    ) {
+        encode_copy(&mut self.encoder, source, range, destination, offset);
+    }
+
+    /// Records a copy of the texture into the buffer.
+    pub fn copy_texture_to_buffer(
+        &mut self,
+        texture: &Texture<Rgba8>,
+        buffer: &impl BufferBinding,
+    ) {
+        texture.encode_copy_to_buffer(&mut self.encoder, buffer);
+    }
+
+    /// Records a write of the bytes into the buffer at `offset`. Unlike the buffers'
+    /// own upload functions, this happens in order with the other recorded commands
+    /// and never grows the buffer.
+    pub fn write_buffer(&mut self, buffer: &impl BufferBinding, offset: u64, data: &[u8]) {
+        let staging = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: data,
+            usage: BufferUsages::COPY_SRC,
+        });
+
+        let buffer = self.gpu.binding_manager.get_resource(buffer.get_id());
+        let buffer = buffer.expect_buffer();
+        self.encoder
+            .copy_buffer_to_buffer(&staging, 0, buffer, offset, data.len() as u64);
+    }
+
+    /// Records an upload of the data to the start of the buffer, laid out like
+    /// the buffers' own upload functions. See [`Self::write_buffer`].
+    pub fn upload<T: ShaderType + WriteInto>(&mut self, buffer: &impl BufferBinding, data: &T) {
+        let mut bytes = Vec::new();
+        StorageBuffer::new(&mut bytes).write(data).unwrap();
+        self.write_buffer(buffer, 0, &bytes);
+    }
+
+    /// Gives access to the underlying command encoder, for commands tufa doesn't wrap.
+    pub fn encoder(&mut self) -> &mut CommandEncoder {
+        &mut self.encoder
+    }
+}
+
+impl Gpu {
+    /// Records commands into a single command encoder and submits them at once,
+    /// after anything already in the dispatch queue. This avoids the overhead of
+    /// a submission per dispatch when running many kernels per step.
+    ///
+    /// ```rust,ignore
+    /// let submission = gpu.record(|batch| {
+    ///     batch.upload(&params, &Params { step });
+    ///     batch.dispatch(&mut integrate, workgroups);
+    ///     batch.dispatch(&mut collide, workgroups);
+    /// });
+    /// submission.wait();
+    /// ```
+    pub fn record(&self, proc: impl FnOnce(&mut Batch)) -> Submission {
+        let encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        let mut batch = Batch { gpu: self, encoder };
+        proc(&mut batch);
+
+        self.submit_dispatch_queue(Some(batch.encoder.finish()))
+    }
+}
@@ -7,7 +7,7 @@ use wgpu::{
     BindingType, Buffer, BufferUsages,
 };
 
-use super::BufferBinding;
+use super::{ops::sealed::Sealed, BufferBinding, BufferOps};
 use crate::{
     bindings::{Bindable, BindableResourceId},
     gpu::Gpu,
@@ -54,7 +54,10 @@ impl Gpu {
         let id = BufferId::new();
         let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
             label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::STORAGE | BufferUsages::BLAS_INPUT,
+            usage: BufferUsages::COPY_DST
+                | BufferUsages::COPY_SRC
+                | BufferUsages::STORAGE
+                | BufferUsages::BLAS_INPUT,
             contents: &buffer,
         });
 
@@ -73,6 +76,16 @@ impl<T> BufferBinding for BlasBuffer<T> {
     }
 }
 
+impl<T: ShaderType + ShaderSize + WriteInto> BufferOps for BlasBuffer<T> {
+    type Element = T;
+}
+
+impl<T> Sealed for BlasBuffer<T> {
+    fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+}
+
 impl<T> Bindable for BlasBuffer<T> {
     fn resource_id(&self) -> BindableResourceId {
         BindableResourceId::Buffer(self.buffer)
@@ -0,0 +1,194 @@
+use std::marker::PhantomData;
+
+use encase::{internal::WriteInto, ShaderType, StorageBuffer};
+use parking_lot::MappedRwLockReadGuard;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindingType, Buffer, BufferDescriptor, BufferUsages,
+};
+
+use crate::{
+    bindings::{Bindable, BindableResourceId},
+    error::Result,
+    gpu::Gpu,
+    misc::ids::BufferId,
+};
+
+use super::BufferBinding;
+
+/// Many values of `T` packed into one uniform buffer, each bound in turn with a
+/// dynamic offset. This lets one pipeline draw many objects with different
+/// uniforms, by passing [`DynamicUniformBuffer::at`] to the `*_dynamic` draw
+/// and dispatch calls. Binds as a `var<uniform>` of a single `T`.
+pub struct DynamicUniformBuffer<T> {
+    gpu: Gpu,
+    buffer: BufferId,
+    stride: u64,
+    _type: PhantomData<T>,
+}
+
+/// Selects which element of a [`DynamicUniformBuffer`] is bound for a draw or dispatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicOffset {
+    pub(crate) buffer: BufferId,
+    pub(crate) offset: u32,
+}
+
+impl<T: ShaderType + WriteInto> DynamicUniformBuffer<T> {
+    fn get(&self) -> MappedRwLockReadGuard<'_, Buffer> {
+        MappedRwLockReadGuard::map(self.gpu.binding_manager.get_resource(self.buffer), |x| {
+            x.expect_buffer()
+        })
+    }
+
+    /// Returns the number of values the buffer holds.
+    pub fn len(&self) -> usize {
+        (self.get().size() / self.stride) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the dynamic offset that binds the value at `index`.
+    pub fn at(&self, index: usize) -> DynamicOffset {
+        assert!(
+            index < self.len(),
+            "Index {index} is out of bounds for a dynamic uniform buffer of length {}",
+            self.len()
+        );
+
+        DynamicOffset {
+            buffer: self.buffer,
+            offset: (index as u64 * self.stride) as u32,
+        }
+    }
+
+    /// Uploads values into the buffer, growing it if needed.
+    pub fn upload(&self, data: &[T]) {
+        let bytes = pack(data, self.stride);
+
+        let this = self.get();
+        if bytes.len() as u64 > this.size() {
+            drop(this);
+            let replacement = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: &bytes,
+                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            });
+
+            let binding_manager = &self.gpu.binding_manager;
+            binding_manager.add_resource(self.buffer, replacement);
+            binding_manager.mark_resource_dirty(&BindableResourceId::Buffer(self.buffer));
+        } else {
+            self.gpu.queue.write_buffer(&this, 0, &bytes);
+        }
+    }
+
+    /// Overwrites the value at `index`, leaving the others untouched.
+    pub fn upload_at(&self, index: usize, data: &T) {
+        let offset = self.at(index).offset as u64;
+        let mut bytes = Vec::new();
+        StorageBuffer::new(&mut bytes).write(data).unwrap();
+        self.gpu.queue.write_buffer(&self.get(), offset, &bytes);
+    }
+}
+
+/// Encodes each value at a multiple of `stride` bytes.
+fn pack<T: ShaderType + WriteInto>(data: &[T], stride: u64) -> Vec<u8> {
+    let mut bytes = vec![0; data.len() * stride as usize];
+    for (value, chunk) in data.iter().zip(bytes.chunks_mut(stride as usize)) {
+        StorageBuffer::new(chunk).write(value).unwrap();
+    }
+    bytes
+}
+
+impl Gpu {
+    /// Creates a new dynamic uniform buffer holding the given values.
+    pub fn create_dynamic_uniform<T>(&self, data: &[T]) -> DynamicUniformBuffer<T>
+    where
+        T: ShaderType + WriteInto,
+    {
+        self.try_create_dynamic_uniform(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new dynamic uniform buffer holding the given values, returning
+    /// an error if wgpu rejects it.
+    pub fn try_create_dynamic_uniform<T>(&self, data: &[T]) -> Result<DynamicUniformBuffer<T>>
+    where
+        T: ShaderType + WriteInto,
+    {
+        let stride = self.dynamic_uniform_stride::<T>();
+        let id = BufferId::new();
+        let buffer = self.error_scope(|| {
+            self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                contents: &pack(data, stride),
+            })
+        })?;
+
+        self.binding_manager.add_resource(id, buffer);
+        Ok(DynamicUniformBuffer {
+            gpu: self.clone(),
+            buffer: id,
+            stride,
+            _type: PhantomData,
+        })
+    }
+
+    /// Creates a new dynamic uniform buffer with room for `count` zeroed values.
+    pub fn create_dynamic_uniform_empty<T>(&self, count: usize) -> DynamicUniformBuffer<T>
+    where
+        T: ShaderType + WriteInto,
+    {
+        let stride = self.dynamic_uniform_stride::<T>();
+        let id = BufferId::new();
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: count as u64 * stride,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+
+        self.binding_manager.add_resource(id, buffer);
+        DynamicUniformBuffer {
+            gpu: self.clone(),
+            buffer: id,
+            stride,
+            _type: PhantomData,
+        }
+    }
+
+    fn dynamic_uniform_stride<T: ShaderType>(&self) -> u64 {
+        let alignment = self.device.limits().min_uniform_buffer_offset_alignment as u64;
+        T::min_size().get().next_multiple_of(alignment)
+    }
+}
+
+impl<T> BufferBinding for DynamicUniformBuffer<T> {
+    fn get_id(&self) -> BufferId {
+        self.buffer
+    }
+}
+
+impl<T: ShaderType> Bindable for DynamicUniformBuffer<T> {
+    fn resource_id(&self) -> BindableResourceId {
+        BindableResourceId::Buffer(self.buffer)
+    }
+
+    fn binding_type(&self) -> BindingType {
+        BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: Some(T::min_size()),
+        }
+    }
+}
+
+impl<T> Drop for DynamicUniformBuffer<T> {
+    fn drop(&mut self) {
+        self.gpu.binding_manager.remove_resource(self.buffer);
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use parking_lot::MappedRwLockReadGuard;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
@@ -10,9 +12,16 @@ use crate::{
     misc::ids::BufferId,
 };
 
-use super::BufferBinding;
+use super::{ops::sealed::Sealed, range, BufferBinding, BufferOps};
+
+const USAGES: BufferUsages = BufferUsages::COPY_DST
+    .union(BufferUsages::COPY_SRC)
+    .union(BufferUsages::STORAGE)
+    .union(BufferUsages::INDIRECT)
+    .union(BufferUsages::INDEX);
 
-/// Represents the indices of a mesh for rendering.
+/// Represents the indices of a mesh for rendering. Like vertex buffers, index
+/// buffers can also be bound as a storage `array<u32>`.
 pub struct IndexBuffer {
     gpu: Gpu,
     buffer: BufferId,
@@ -34,7 +43,7 @@ impl IndexBuffer {
             let replacement = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
                 label: None,
                 contents: buffer,
-                usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
+                usage: USAGES,
             });
 
             let binding_manager = &self.gpu.binding_manager;
@@ -44,6 +53,17 @@ impl IndexBuffer {
             self.gpu.queue.write_buffer(&this, 0, buffer);
         }
     }
+
+    /// Overwrites the indices starting at index `offset`, without growing the buffer.
+    pub fn upload_range(&self, offset: usize, data: &[u32]) {
+        range::write(&self.gpu, &self.get(), offset, data);
+    }
+
+    /// Downloads the indices in the range, in a blocking manner.
+    pub fn download_range(&self, range: Range<usize>) -> Vec<u32> {
+        let buffer = self.get().clone();
+        range::read(&self.gpu, &buffer, range)
+    }
 }
 
 impl Gpu {
@@ -51,7 +71,7 @@ impl Gpu {
         let id = BufferId::new();
         let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
             label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
+            usage: USAGES,
             contents: bytemuck::cast_slice(data),
         });
 
@@ -67,7 +87,7 @@ impl Gpu {
         let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: (size * std::mem::size_of::<u32>()) as u64,
-            usage: BufferUsages::COPY_DST | BufferUsages::INDEX,
+            usage: USAGES,
             mapped_at_creation: false,
         });
 
@@ -85,6 +105,16 @@ impl BufferBinding for IndexBuffer {
     }
 }
 
+impl BufferOps for IndexBuffer {
+    type Element = u32;
+}
+
+impl Sealed for IndexBuffer {
+    fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+}
+
 impl Bindable for IndexBuffer {
     fn resource_id(&self) -> BindableResourceId {
         BindableResourceId::Buffer(self.buffer)
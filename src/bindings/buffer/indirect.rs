@@ -0,0 +1,173 @@
+use std::{marker::PhantomData, mem, ops::Range};
+
+use bytemuck::Pod;
+use parking_lot::MappedRwLockReadGuard;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindingType, Buffer, BufferDescriptor, BufferUsages,
+};
+
+pub use wgpu::util::{DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs};
+
+use crate::{
+    bindings::{Bindable, BindableResourceId},
+    error::Result,
+    gpu::Gpu,
+    misc::ids::BufferId,
+};
+
+use super::BufferBinding;
+
+const USAGES: BufferUsages = BufferUsages::COPY_DST
+    .union(BufferUsages::COPY_SRC)
+    .union(BufferUsages::STORAGE)
+    .union(BufferUsages::INDIRECT);
+
+/// The arguments of an indirect dispatch or draw call.
+pub trait IndirectArgs: Pod + sealed::Sealed {}
+
+impl IndirectArgs for DispatchIndirectArgs {}
+impl IndirectArgs for DrawIndirectArgs {}
+impl IndirectArgs for DrawIndexedIndirectArgs {}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::DispatchIndirectArgs {}
+    impl Sealed for super::DrawIndirectArgs {}
+    impl Sealed for super::DrawIndexedIndirectArgs {}
+}
+
+/// An array of dispatch or draw arguments that are read by the GPU, so the
+/// number of workgroups or vertices can be decided by a shader. Binds as a
+/// `var<storage, read_write>` array of `u32`s or of a matching struct.
+pub struct IndirectBuffer<T> {
+    gpu: Gpu,
+    buffer: BufferId,
+    _type: PhantomData<T>,
+}
+
+impl<T: IndirectArgs> IndirectBuffer<T> {
+    pub(crate) fn get(&self) -> MappedRwLockReadGuard<'_, Buffer> {
+        MappedRwLockReadGuard::map(self.gpu.binding_manager.get_resource(self.buffer), |x| {
+            x.expect_buffer()
+        })
+    }
+
+    /// Returns a handle to the buffer, for encoding passes without holding the
+    /// resource lock, which encoding may need to take again.
+    pub(crate) fn buffer(&self) -> Buffer {
+        self.get().clone()
+    }
+
+    /// Byte offset of the first entry in the range, panicking if it is out of bounds.
+    pub(crate) fn offset(&self, entries: Range<u32>) -> u64 {
+        assert!(
+            entries.start <= entries.end && entries.end <= self.len(),
+            "Indirect entries {entries:?} are out of bounds for a buffer of length {}",
+            self.len()
+        );
+        entries.start as u64 * mem::size_of::<T>() as u64
+    }
+
+    /// Returns the number of arguments the buffer holds.
+    pub fn len(&self) -> u32 {
+        (self.get().size() / mem::size_of::<T>() as u64) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Uploads arguments into the buffer, growing it if needed.
+    pub fn upload(&self, data: &[T]) {
+        let bytes = bytemuck::cast_slice(data);
+
+        let this = self.get();
+        if bytes.len() as u64 > this.size() {
+            drop(this);
+            let replacement = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: bytes,
+                usage: USAGES,
+            });
+
+            let binding_manager = &self.gpu.binding_manager;
+            binding_manager.add_resource(self.buffer, replacement);
+            binding_manager.mark_resource_dirty(&BindableResourceId::Buffer(self.buffer));
+        } else {
+            self.gpu.queue.write_buffer(&this, 0, bytes);
+        }
+    }
+}
+
+impl Gpu {
+    /// Creates a new indirect buffer holding the given arguments.
+    pub fn create_indirect<T: IndirectArgs>(&self, data: &[T]) -> IndirectBuffer<T> {
+        self.try_create_indirect(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new indirect buffer holding the given arguments, returning an
+    /// error if wgpu rejects it.
+    pub fn try_create_indirect<T: IndirectArgs>(&self, data: &[T]) -> Result<IndirectBuffer<T>> {
+        let id = BufferId::new();
+        let buffer = self.error_scope(|| {
+            self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                usage: USAGES,
+                contents: bytemuck::cast_slice(data),
+            })
+        })?;
+
+        self.binding_manager.add_resource(id, buffer);
+        Ok(IndirectBuffer {
+            gpu: self.clone(),
+            buffer: id,
+            _type: PhantomData,
+        })
+    }
+
+    /// Creates a new indirect buffer with room for `count` zeroed arguments.
+    pub fn create_indirect_empty<T: IndirectArgs>(&self, count: u32) -> IndirectBuffer<T> {
+        let id = BufferId::new();
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: count as u64 * mem::size_of::<T>() as u64,
+            usage: USAGES,
+            mapped_at_creation: false,
+        });
+
+        self.binding_manager.add_resource(id, buffer);
+        IndirectBuffer {
+            gpu: self.clone(),
+            buffer: id,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> BufferBinding for IndirectBuffer<T> {
+    fn get_id(&self) -> BufferId {
+        self.buffer
+    }
+}
+
+impl<T> Bindable for IndirectBuffer<T> {
+    fn resource_id(&self) -> BindableResourceId {
+        BindableResourceId::Buffer(self.buffer)
+    }
+
+    fn binding_type(&self) -> BindingType {
+        BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+}
+
+impl<T> Drop for IndirectBuffer<T> {
+    fn drop(&mut self) {
+        self.gpu.binding_manager.remove_resource(self.buffer);
+    }
+}
@@ -3,16 +3,32 @@
 use crate::misc::ids::BufferId;
 
 mod blas;
+mod dynamic_uniform;
 mod index;
+mod indirect;
 pub mod mutability;
+pub(crate) mod ops;
+mod ping_pong;
+mod pod;
+mod range;
+mod readback;
 mod storage;
 mod uniform;
+mod vec;
 mod vertex;
 
 pub use blas::BlasBuffer;
+pub use dynamic_uniform::{DynamicOffset, DynamicUniformBuffer};
 pub use index::IndexBuffer;
+pub use indirect::{
+    DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs, IndirectArgs, IndirectBuffer,
+};
+pub use ops::BufferOps;
+pub use ping_pong::PingPong;
+pub use readback::ReadbackRing;
 pub use storage::StorageBuffer;
 pub use uniform::UniformBuffer;
+pub use vec::GpuVec;
 pub use vertex::VertexBuffer;
 
 /// Represents a buffer that can be bound to a pipline.
@@ -0,0 +1,127 @@
+use std::{ops::Range, slice};
+
+use encase::{internal::WriteInto, ShaderSize, ShaderType, StorageBuffer};
+use wgpu::{BufferUsages, CommandEncoder};
+
+use crate::submission::Submission;
+
+use super::{range::byte_range, BufferBinding};
+
+/// Clearing, filling and copying the elements of a buffer on the GPU. Like
+/// dispatches, each operation runs right away and returns a [`Submission`],
+/// or can be queued to run with the next dispatch using the `queue_*` versions.
+/// They can also be recorded into a [`crate::batch::Batch`].
+pub trait BufferOps: BufferBinding + sealed::Sealed {
+    /// The type of the elements in the buffer.
+    type Element: ShaderType + ShaderSize + WriteInto;
+
+    /// Sets every byte of the buffer to zero.
+    fn clear(&self) -> Submission {
+        self.gpu()
+            .immediate_dispatch(|encoder| encode_clear(encoder, self))
+    }
+
+    /// Queues the buffer to be cleared like [`Self::clear`].
+    fn queue_clear(&self) {
+        self.gpu()
+            .queue_dispatch(|encoder| encode_clear(encoder, self));
+    }
+
+    /// Sets every element of the buffer to the value.
+    fn fill(&self, value: &Self::Element) -> Submission {
+        self.gpu()
+            .immediate_dispatch(|encoder| encode_fill(encoder, self, value))
+    }
+
+    /// Queues the buffer to be filled like [`Self::fill`].
+    fn queue_fill(&self, value: &Self::Element) {
+        self.gpu()
+            .queue_dispatch(|encoder| encode_fill(encoder, self, value));
+    }
+
+    /// Copies the elements in `range` of the source buffer into this one,
+    /// starting at element `offset`. Panics if either side is out of bounds.
+    fn copy_from(
+        &self,
+        source: &impl BufferOps<Element = Self::Element>,
+        range: Range<usize>,
+        offset: usize,
+    ) -> Submission {
+        self.gpu()
+            .immediate_dispatch(|encoder| encode_copy(encoder, source, range, self, offset))
+    }
+
+    /// Queues a copy between the buffers like [`Self::copy_from`].
+    fn queue_copy_from(
+        &self,
+        source: &impl BufferOps<Element = Self::Element>,
+        range: Range<usize>,
+        offset: usize,
+    ) {
+        self.gpu()
+            .queue_dispatch(|encoder| encode_copy(encoder, source, range, self, offset));
+    }
+}
+
+pub(crate) mod sealed {
+    use crate::gpu::Gpu;
+
+    pub trait Sealed {
+        fn gpu(&self) -> &Gpu;
+    }
+}
+
+pub(crate) fn encode_clear<B: BufferOps + ?Sized>(encoder: &mut CommandEncoder, buffer: &B) {
+    let buffer = buffer.gpu().binding_manager.get_resource(buffer.get_id());
+    encoder.clear_buffer(buffer.expect_buffer(), 0, None);
+}
+
+/// Fills the buffer with the value, using the fill kernel for non-zero values.
+/// Every buffer implementing [`BufferOps`] has `STORAGE` usage, so the kernel can bind it.
+pub(crate) fn encode_fill<B: BufferOps + ?Sized>(
+    encoder: &mut CommandEncoder,
+    buffer: &B,
+    value: &B::Element,
+) {
+    let mut pattern = Vec::new();
+    StorageBuffer::new(&mut pattern)
+        .write(slice::from_ref(value))
+        .unwrap();
+    if pattern.iter().all(|&x| x == 0) {
+        return encode_clear(encoder, buffer);
+    }
+
+    let gpu = buffer.gpu();
+    let [buffer] = gpu.binding_manager.get_buffers([buffer.get_id()]);
+    debug_assert!(buffer.usage().contains(BufferUsages::STORAGE));
+    gpu.fill_kernel
+        .encode(&gpu.device, encoder, &buffer, &pattern);
+}
+
+pub(crate) fn encode_copy<S: BufferOps + ?Sized, D: BufferOps<Element = S::Element> + ?Sized>(
+    encoder: &mut CommandEncoder,
+    source: &S,
+    range: Range<usize>,
+    destination: &D,
+    offset: usize,
+) {
+    assert!(
+        source.get_id() != destination.get_id(),
+        "Can't copy between elements of the same buffer"
+    );
+
+    let [source, destination] = source
+        .gpu()
+        .binding_manager
+        .get_buffers([source.get_id(), destination.get_id()]);
+
+    let bytes = byte_range::<S::Element>(&source, range.clone());
+    let target = byte_range::<S::Element>(&destination, offset..offset + range.len());
+    encoder.copy_buffer_to_buffer(
+        &source,
+        bytes.start,
+        &destination,
+        target.start,
+        bytes.end - bytes.start,
+    );
+}
@@ -0,0 +1,131 @@
+use encase::{
+    internal::{CreateFrom, WriteInto},
+    ShaderType,
+};
+
+use crate::{
+    bindings::buffer::{
+        mutability::{Immutable, Mutable},
+        StorageBuffer,
+    },
+    error::Result,
+    gpu::Gpu,
+};
+
+/// A pair of storage buffers for simulations that read last step's state and
+/// write the next. Bind [`PingPong::read`] and [`PingPong::write`] to the same
+/// pipeline, then call [`PingPong::swap`] after each step to flip which buffer
+/// each binding points at. The bind groups for both sides are cached, so
+/// swapping doesn't recreate them every step.
+///
+/// ```rust,ignore
+/// let cells = gpu.create_ping_pong(&initial);
+/// let mut step = gpu
+///     .compute_pipeline(include_wgsl!("life.wgsl"))
+///     .bind(cells.read())
+///     .bind(cells.write())
+///     .finish();
+///
+/// for _ in 0..steps {
+///     step.dispatch(workgroups);
+///     cells.swap();
+/// }
+/// let result = cells.read().download();
+/// ```
+pub struct PingPong<T> {
+    read: StorageBuffer<T, Immutable>,
+    write: StorageBuffer<T, Mutable>,
+}
+
+impl<T: ShaderType + WriteInto + CreateFrom> PingPong<T> {
+    /// The buffer holding the last written state, bound as `var<storage, read>`.
+    pub fn read(&self) -> &StorageBuffer<T, Immutable> {
+        &self.read
+    }
+
+    /// The buffer to write the next state into, bound as `var<storage, read_write>`.
+    pub fn write(&self) -> &StorageBuffer<T, Mutable> {
+        &self.write
+    }
+
+    /// Flips the buffers, so the state just written becomes the one read.
+    pub fn swap(&self) {
+        let binding_manager = &self.read.gpu.binding_manager;
+        binding_manager.swap_buffers(self.read.buffer, self.write.buffer);
+    }
+
+    /// Uploads data into both buffers.
+    pub fn upload(&self, data: &T) {
+        self.read.upload(data);
+        self.write.upload(data);
+    }
+}
+
+impl Gpu {
+    /// Creates a new ping-pong pair, with both buffers holding the given initial state.
+    pub fn create_ping_pong<T>(&self, data: &T) -> PingPong<T>
+    where
+        T: ShaderType + WriteInto + CreateFrom,
+    {
+        self.try_create_ping_pong(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new ping-pong pair, returning an error if wgpu rejects the buffers.
+    pub fn try_create_ping_pong<T>(&self, data: &T) -> Result<PingPong<T>>
+    where
+        T: ShaderType + WriteInto + CreateFrom,
+    {
+        Ok(PingPong {
+            read: self.try_create_storage(data)?,
+            write: self.try_create_storage(data)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use wgpu::{ShaderModuleDescriptor, ShaderSource};
+
+    use crate::gpu::Gpu;
+
+    const STEP: &str = "
+        @group(0) @binding(0) var<storage, read> last: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> next: array<u32>;
+
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+            next[id.x] = last[id.x] + 1u;
+        }
+    ";
+
+    #[test]
+    fn swap_then_dispatch() {
+        let Ok(gpu) = Gpu::new() else {
+            return;
+        };
+        let cells = gpu.create_ping_pong(&vec![0u32; 4]);
+        let mut step = gpu
+            .compute_pipeline(ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(STEP.into()),
+            })
+            .bind(cells.read())
+            .bind(cells.write())
+            .finish();
+
+        for _ in 0..5 {
+            step.dispatch(Vector3::new(4, 1, 1));
+            cells.swap();
+        }
+        assert_eq!(cells.read().download(), [5; 4]);
+        assert_eq!(cells.write().download(), [4; 4]);
+
+        // Reallocated buffers get new bind groups instead of stale cached ones.
+        cells.upload(&vec![10; 8]);
+        step.dispatch(Vector3::new(8, 1, 1));
+        cells.swap();
+        assert_eq!(cells.read().download(), [11; 8]);
+    }
+}
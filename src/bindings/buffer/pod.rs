@@ -0,0 +1,29 @@
+use std::mem;
+
+use bytemuck::Pod;
+use encase::{internal::WriteInto, ShaderSize, ShaderType, StorageBuffer};
+
+/// Casts the elements to bytes without going through encase. The size of `T`
+/// must match its WGSL array stride, which is checked at compile time. In debug
+/// builds, the first element is also compared against its encase encoding to
+/// catch fields at different offsets.
+pub(crate) fn cast<T: Pod + ShaderType + ShaderSize + WriteInto>(data: &[T]) -> &[u8] {
+    const {
+        assert!(
+            mem::size_of::<T>() as u64 == <[T; 1]>::SHADER_SIZE.get(),
+            "Pod type size doesn't match its WGSL array stride, add explicit padding"
+        )
+    };
+
+    let bytes = bytemuck::cast_slice(data);
+    if cfg!(debug_assertions) && !data.is_empty() {
+        let mut expected = Vec::<u8>::new();
+        StorageBuffer::new(&mut expected).write(&data[..1]).unwrap();
+        assert!(
+            expected == bytes[..expected.len()],
+            "Pod type layout doesn't match WGSL layout rules"
+        );
+    }
+
+    bytes
+}
@@ -0,0 +1,70 @@
+use std::ops::Range;
+
+use encase::{
+    internal::{CreateFrom, WriteInto},
+    DynamicStorageBuffer, ShaderSize, StorageBuffer,
+};
+use wgpu::{Buffer, COPY_BUFFER_ALIGNMENT};
+
+use crate::{gpu::Gpu, misc::staging::Readback};
+
+/// Byte stride between elements of a WGSL array of `T`.
+pub(crate) fn stride<T: ShaderSize>() -> u64 {
+    <[T; 1]>::SHADER_SIZE.get()
+}
+
+/// Byte range covered by the elements, panicking if it is out of bounds.
+pub(crate) fn byte_range<T: ShaderSize>(buffer: &Buffer, elements: Range<usize>) -> Range<u64> {
+    let stride = stride::<T>();
+    let bytes = elements.start as u64 * stride..elements.end as u64 * stride;
+    assert!(
+        elements.start <= elements.end && bytes.end <= buffer.size(),
+        "Elements {elements:?} are out of bounds for a buffer of length {}",
+        buffer.size() / stride
+    );
+    bytes
+}
+
+/// Writes the elements into the buffer, starting at element `offset`.
+pub(crate) fn write<T: ShaderSize + WriteInto>(
+    gpu: &Gpu,
+    buffer: &Buffer,
+    offset: usize,
+    data: &[T],
+) {
+    let bytes = byte_range::<T>(buffer, offset..offset + data.len());
+    if bytes.is_empty() {
+        return;
+    }
+
+    let mut encoded = Vec::new();
+    StorageBuffer::new(&mut encoded).write(&data).unwrap();
+
+    // Strides of host-shareable types are always a multiple of four bytes.
+    debug_assert_eq!(bytes.start % COPY_BUFFER_ALIGNMENT, 0);
+    debug_assert_eq!(encoded.len() as u64 % COPY_BUFFER_ALIGNMENT, 0);
+    gpu.queue.write_buffer(buffer, bytes.start, &encoded);
+}
+
+/// Reads the elements in the range back from the buffer, blocking until they arrive.
+/// Callers pass a cloned handle, so the binding manager isn't locked while blocking.
+pub(crate) fn read<T: ShaderSize + CreateFrom>(
+    gpu: &Gpu,
+    buffer: &Buffer,
+    elements: Range<usize>,
+) -> Vec<T> {
+    let bytes = byte_range::<T>(buffer, elements);
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+
+    let start = bytes.start / COPY_BUFFER_ALIGNMENT * COPY_BUFFER_ALIGNMENT;
+    let end = bytes.end.next_multiple_of(COPY_BUFFER_ALIGNMENT);
+    let data = Readback::new(gpu, end - start, |encoder, staging| {
+        encoder.copy_buffer_to_buffer(buffer, start, staging, 0, end - start);
+    })
+    .wait();
+
+    let data = &data[(bytes.start - start) as usize..(bytes.end - start) as usize];
+    DynamicStorageBuffer::new(data).create().unwrap()
+}
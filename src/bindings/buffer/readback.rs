@@ -0,0 +1,93 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+use encase::{
+    internal::{CreateFrom, WriteInto},
+    DynamicStorageBuffer, ShaderType,
+};
+
+use crate::{
+    bindings::{
+        buffer::{mutability::Mutability, StorageBuffer},
+        texture::{format::Rgba8, Texture},
+    },
+    gpu::Gpu,
+    misc::staging::Readback,
+};
+
+/// Downloads a buffer or texture every frame while keeping up to `capacity`
+/// readbacks in flight, so the GPU isn't stalled waiting on the CPU. Each push
+/// uses a pooled staging buffer, so memory use is bounded by the capacity.
+///
+/// Results come out in the order they were pushed.
+pub struct ReadbackRing<T> {
+    capacity: usize,
+    in_flight: VecDeque<Readback>,
+    _type: PhantomData<T>,
+}
+
+impl<T: ShaderType + WriteInto + CreateFrom> ReadbackRing<T> {
+    /// Starts downloading the buffer. If the ring is full, this first waits
+    /// for the oldest readback and returns it.
+    pub fn push<Mut: Mutability>(&mut self, buffer: &StorageBuffer<T, Mut>) -> Option<T> {
+        let oldest = self.make_room();
+        self.in_flight.push_back(buffer.readback());
+        oldest
+    }
+
+    /// Starts downloading the texture, like [`Self::push`]. The pixels are read
+    /// as `T`, usually a `Vec<u32>` with one packed RGBA color per pixel.
+    pub fn push_texture(&mut self, texture: &Texture<Rgba8>) -> Option<T> {
+        let oldest = self.make_room();
+        self.in_flight.push_back(texture.readback());
+        oldest
+    }
+
+    /// Returns the oldest readback if it has finished, without blocking.
+    pub fn try_pop(&mut self) -> Option<T> {
+        if !self.in_flight.front()?.is_done() {
+            return None;
+        }
+        self.pop()
+    }
+
+    /// Waits for the oldest readback and returns it, or `None` if the ring is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let data = self.in_flight.pop_front()?.wait();
+        let mut store = DynamicStorageBuffer::new(data);
+        Some(store.create().unwrap())
+    }
+
+    /// Returns the number of readbacks in flight.
+    pub fn len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn make_room(&mut self) -> Option<T> {
+        (self.in_flight.len() >= self.capacity)
+            .then(|| self.pop())
+            .flatten()
+    }
+}
+
+impl Gpu {
+    /// Creates a readback ring that keeps up to `capacity` downloads in flight.
+    pub fn create_readback_ring<T>(&self, capacity: usize) -> ReadbackRing<T> {
+        assert!(
+            capacity > 0,
+            "A readback ring needs a capacity of at least one"
+        );
+        ReadbackRing {
+            capacity,
+            in_flight: VecDeque::with_capacity(capacity),
+            _type: PhantomData,
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use std::{marker::PhantomData, thread};
+use std::{future::Future, marker::PhantomData, ops::Range};
 
+use bytemuck::Pod;
 use encase::{
     internal::{CreateFrom, WriteInto},
-    DynamicStorageBuffer, ShaderType,
+    DynamicStorageBuffer, ShaderSize, ShaderType,
 };
 use parking_lot::MappedRwLockReadGuard;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindingType, Buffer, BufferDescriptor, BufferUsages, MaintainBase, MapMode,
+    BindingType, Buffer, BufferDescriptor, BufferUsages,
 };
 
 use crate::{
@@ -15,11 +16,12 @@ use crate::{
         buffer::mutability::{Immutable, Mutability, Mutable},
         Bindable, BindableResourceId,
     },
+    error::Result,
     gpu::Gpu,
-    misc::{ids::BufferId, thread_ptr::ThreadSafePtr},
+    misc::{ids::BufferId, staging::Readback},
 };
 
-use super::BufferBinding;
+use super::{ops::sealed::Sealed, pod, range, BufferBinding, BufferOps};
 
 /// A storage buffer is a buffer that can be read from or written to in the shader
 pub struct StorageBuffer<T, Mut: Mutability> {
@@ -51,7 +53,10 @@ impl<T: ShaderType + WriteInto + CreateFrom, Mut: Mutability> StorageBuffer<T, M
         let mut bytes = Vec::new();
         let mut storage = encase::StorageBuffer::new(&mut bytes);
         storage.write(data).unwrap();
+        self.upload_bytes(&bytes, shrink);
+    }
 
+    fn upload_bytes(&self, bytes: &[u8], shrink: bool) {
         let buffer = self.get();
         let current_size = buffer.size() as usize;
 
@@ -59,7 +64,7 @@ impl<T: ShaderType + WriteInto + CreateFrom, Mut: Mutability> StorageBuffer<T, M
             drop(buffer);
             let replacement = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
                 label: None,
-                contents: &bytes,
+                contents: bytes,
                 usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
             });
 
@@ -67,95 +72,143 @@ impl<T: ShaderType + WriteInto + CreateFrom, Mut: Mutability> StorageBuffer<T, M
             binding_manager.add_resource(self.buffer, replacement);
             binding_manager.mark_resource_dirty(&BindableResourceId::Buffer(self.buffer));
         } else {
-            self.gpu.queue.write_buffer(&buffer, 0, &bytes);
+            self.gpu.queue.write_buffer(&buffer, 0, bytes);
         }
     }
 
     /// Downloads the buffer from the GPU in a blocking manner. This can be
     /// pretty slow.
     pub fn download(&self) -> T {
-        let buffer = self.get();
-        let staging = self.gpu.device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: buffer.size(),
-            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
-
-        self.gpu.immediate_dispatch(|encoder| {
-            encoder.copy_buffer_to_buffer(&buffer, 0, &staging, 0, buffer.size());
-        });
-
-        let slice = staging.slice(..);
-
-        let (tx, rx) = crossbeam_channel::bounded(1);
-        slice.map_async(MapMode::Read, move |_| tx.send(()).unwrap());
-
-        let poll = self.gpu.device.poll(MaintainBase::Wait).unwrap();
-        assert!(poll.wait_finished());
-        rx.recv().unwrap();
-
-        let data = slice.get_mapped_range().to_vec();
-        let mut store = encase::DynamicStorageBuffer::new(data);
-
+        let data = self.readback().wait();
+        let mut store = DynamicStorageBuffer::new(data);
         store.create().unwrap()
     }
 
-    /// Requests the download of the buffer. The provided callback will be
-    /// executed once the transfer finishes.
-    pub fn download_async(&self, func: impl FnOnce(T) + Send + 'static) {
-        let buffer = self.get();
-        let staging = self.gpu.device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: buffer.size(),
-            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
-        let staging = Box::leak(Box::new(staging));
+    /// Requests the download of the buffer. The returned future resolves once
+    /// the transfer finishes and can be awaited from any executor, as the device
+    /// is polled on a background thread in the meantime.
+    pub fn download_async(&self) -> impl Future<Output = T> + Send + 'static
+    where
+        T: 'static,
+    {
+        let data = self.readback().future();
+        async move {
+            let mut store = DynamicStorageBuffer::new(data.await);
+            store.create().unwrap()
+        }
+    }
 
-        self.gpu.immediate_dispatch(|encoder| {
+    /// Starts copying the whole buffer into a pooled staging buffer.
+    pub(crate) fn readback(&self) -> Readback {
+        let buffer = self.get().clone();
+        Readback::new(&self.gpu, buffer.size(), |encoder, staging| {
             encoder.copy_buffer_to_buffer(&buffer, 0, staging, 0, buffer.size());
-        });
+        })
+    }
+}
 
-        let staging = ThreadSafePtr(staging as *mut Buffer);
-        let slice = staging.deref().slice(..);
+impl<E, Mut: Mutability> StorageBuffer<Vec<E>, Mut>
+where
+    E: ShaderType + ShaderSize + WriteInto + CreateFrom,
+{
+    /// Overwrites the elements starting at element `offset`, leaving the rest
+    /// of the buffer untouched. Panics if the elements don't fit in the buffer.
+    pub fn upload_range(&self, offset: usize, data: &[E]) {
+        range::write(&self.gpu, &self.get(), offset, data);
+    }
 
-        slice.map_async(MapMode::Read, move |_| {
-            let data = slice.get_mapped_range().to_vec();
-            unsafe { drop(Box::from_raw(staging.deref_mut())) };
+    /// Downloads only the elements in the range, in a blocking manner.
+    pub fn download_range(&self, range: Range<usize>) -> Vec<E> {
+        let buffer = self.get().clone();
+        range::read(&self.gpu, &buffer, range)
+    }
 
-            thread::spawn(move || {
-                let mut store = DynamicStorageBuffer::new(data);
-                func(store.create().unwrap());
-            });
-        });
+    /// Uploads data into the buffer by casting it to bytes, skipping encase.
+    /// See [`Gpu::create_storage_pod`].
+    pub fn upload_pod(&self, data: &[E])
+    where
+        E: Pod,
+    {
+        self.upload_bytes(pod::cast(data), false);
+    }
+
+    /// Downloads the buffer by casting its bytes back, skipping encase. This
+    /// is blocking and can be pretty slow.
+    pub fn download_pod(&self) -> Vec<E>
+    where
+        E: Pod,
+    {
+        bytemuck::pod_collect_to_vec(&self.readback().wait())
     }
 }
 
 impl Gpu {
+    /// Creates a new storage buffer with the given initial state
     pub fn create_storage<T, Mut: Mutability>(&self, data: &T) -> StorageBuffer<T, Mut>
+    where
+        T: ShaderType + WriteInto + CreateFrom,
+    {
+        self.try_create_storage(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new storage buffer with the given initial state, returning an
+    /// error if wgpu rejects it (for example, if it exceeds the device limits).
+    pub fn try_create_storage<T, Mut: Mutability>(&self, data: &T) -> Result<StorageBuffer<T, Mut>>
     where
         T: ShaderType + WriteInto + CreateFrom,
     {
         let mut buffer = Vec::new();
         let mut storage = encase::StorageBuffer::new(&mut buffer);
         storage.write(data).unwrap();
+        self.try_create_storage_bytes(&buffer)
+    }
+
+    /// Creates a new storage buffer from a slice of [`Pod`] data, which is cast
+    /// straight to bytes instead of being serialized with encase. The type's
+    /// size must match its WGSL array stride and, in debug builds, its encase
+    /// layout, so vectors like `vec3<f32>` need explicit padding.
+    pub fn create_storage_pod<T, Mut: Mutability>(&self, data: &[T]) -> StorageBuffer<Vec<T>, Mut>
+    where
+        T: Pod + ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        self.try_create_storage_pod(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new storage buffer from a slice of [`Pod`] data, returning an
+    /// error if wgpu rejects it. See [`Gpu::create_storage_pod`].
+    pub fn try_create_storage_pod<T, Mut: Mutability>(
+        &self,
+        data: &[T],
+    ) -> Result<StorageBuffer<Vec<T>, Mut>>
+    where
+        T: Pod + ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        self.try_create_storage_bytes(pod::cast(data))
+    }
 
+    fn try_create_storage_bytes<T, Mut: Mutability>(
+        &self,
+        contents: &[u8],
+    ) -> Result<StorageBuffer<T, Mut>> {
         let id = BufferId::new();
-        let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
-            contents: &buffer,
-        });
+        let buffer = self.error_scope(|| {
+            self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
+                contents,
+            })
+        })?;
 
         self.binding_manager.add_resource(id, buffer);
-        StorageBuffer {
+        Ok(StorageBuffer {
             gpu: self.clone(),
             buffer: id,
 
             _type: PhantomData,
             _mut: PhantomData,
-        }
+        })
     }
 
     pub fn create_storage_empty<T, Mut: Mutability>(&self, size: u64) -> StorageBuffer<T, Mut>
@@ -187,6 +240,18 @@ impl<T, Mut: Mutability> BufferBinding for StorageBuffer<T, Mut> {
     }
 }
 
+impl<E: ShaderType + ShaderSize + WriteInto, Mut: Mutability> BufferOps
+    for StorageBuffer<Vec<E>, Mut>
+{
+    type Element = E;
+}
+
+impl<T, Mut: Mutability> Sealed for StorageBuffer<T, Mut> {
+    fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+}
+
 impl<T: ShaderType + WriteInto + CreateFrom> Bindable for StorageBuffer<T, Mutable> {
     fn resource_id(&self) -> BindableResourceId {
         BindableResourceId::Buffer(self.buffer)
@@ -12,6 +12,7 @@ use wgpu::{
 
 use crate::{
     bindings::{Bindable, BindableResourceId},
+    error::Result,
     gpu::Gpu,
     misc::ids::BufferId,
 };
@@ -45,6 +46,16 @@ impl<T: ShaderType + WriteInto + CreateFrom> UniformBuffer<T> {
 impl Gpu {
     /// Creates a new uniform buffer with the given initial state
     pub fn create_uniform<T>(&self, data: &T) -> UniformBuffer<T>
+    where
+        T: ShaderType + WriteInto + CreateFrom,
+    {
+        self.try_create_uniform(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new uniform buffer with the given initial state, returning an
+    /// error if wgpu rejects it.
+    pub fn try_create_uniform<T>(&self, data: &T) -> Result<UniformBuffer<T>>
     where
         T: ShaderType + WriteInto + CreateFrom,
     {
@@ -53,18 +64,20 @@ impl Gpu {
         storage.write(data).unwrap();
 
         let id = BufferId::new();
-        let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
-            contents: &buffer,
-        });
+        let buffer = self.error_scope(|| {
+            self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                contents: &buffer,
+            })
+        })?;
 
         self.binding_manager.add_resource(id, buffer);
-        UniformBuffer {
+        Ok(UniformBuffer {
             gpu: self.clone(),
             buffer: id,
             _type: PhantomData,
-        }
+        })
     }
 }
 
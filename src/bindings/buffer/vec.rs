@@ -0,0 +1,253 @@
+use std::marker::PhantomData;
+
+use encase::{
+    internal::{CreateFrom, WriteInto},
+    ShaderSize, ShaderType,
+};
+use parking_lot::MappedRwLockReadGuard;
+use wgpu::{BindingType, Buffer, BufferDescriptor, BufferUsages};
+
+use crate::{
+    bindings::{
+        buffer::mutability::{Immutable, Mutability, Mutable},
+        Bindable, BindableResourceId,
+    },
+    error::Result,
+    gpu::Gpu,
+    misc::ids::BufferId,
+};
+
+use super::{
+    ops::sealed::Sealed,
+    range::{self, stride},
+    BufferBinding, BufferOps, UniformBuffer,
+};
+
+const USAGES: BufferUsages = BufferUsages::COPY_DST
+    .union(BufferUsages::COPY_SRC)
+    .union(BufferUsages::STORAGE);
+
+/// A growable array on the GPU. Pushing past the capacity doubles it, copying
+/// the existing elements over on the GPU instead of uploading them again.
+///
+/// Binds as a storage `array<T>`, whose `arrayLength` is the capacity. The
+/// number of elements is kept in the `u32` uniform from [`GpuVec::length`].
+pub struct GpuVec<T, Mut: Mutability> {
+    gpu: Gpu,
+    buffer: BufferId,
+    len: usize,
+    capacity: usize,
+    length: UniformBuffer<u32>,
+
+    _type: PhantomData<T>,
+    _mut: PhantomData<Mut>,
+}
+
+impl<T, Mut: Mutability> GpuVec<T, Mut>
+where
+    T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+{
+    fn get(&self) -> MappedRwLockReadGuard<'_, Buffer> {
+        MappedRwLockReadGuard::map(self.gpu.binding_manager.get_resource(self.buffer), |x| {
+            x.expect_buffer()
+        })
+    }
+
+    /// Returns the number of elements in the vec.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements the vec can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The uniform holding the number of elements, to bind next to the vec.
+    pub fn length(&self) -> &UniformBuffer<u32> {
+        &self.length
+    }
+
+    /// Appends an element to the end of the vec.
+    pub fn push(&mut self, value: T) {
+        self.extend(&[value]);
+    }
+
+    /// Appends the elements to the end of the vec, growing it if needed.
+    pub fn extend(&mut self, data: &[T]) {
+        self.reserve(data.len());
+        range::write(&self.gpu, &self.get(), self.len, data);
+        self.set_len(self.len + data.len());
+    }
+
+    /// Shortens the vec to `len` elements. The capacity is left unchanged.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.set_len(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Makes room for at least `additional` more elements, at least doubling
+    /// the capacity when it grows.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len + additional;
+        if required <= self.capacity {
+            return;
+        }
+
+        let capacity = required.max(self.capacity * 2);
+        let replacement = self.gpu.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: capacity as u64 * stride::<T>(),
+            usage: USAGES,
+            mapped_at_creation: false,
+        });
+
+        // Queued copies run after the dispatches already queued against the old
+        // buffer, and `write_buffer` calls into the new one only touch the
+        // elements past `len`, so nothing needs to be flushed here.
+        let old = self.get().clone();
+        let used = self.len as u64 * stride::<T>();
+        self.gpu.queue_dispatch(|encoder| {
+            encoder.copy_buffer_to_buffer(&old, 0, &replacement, 0, used);
+        });
+
+        let binding_manager = &self.gpu.binding_manager;
+        binding_manager.add_resource(self.buffer, replacement);
+        binding_manager.mark_resource_dirty(&BindableResourceId::Buffer(self.buffer));
+        self.capacity = capacity;
+    }
+
+    /// Downloads the elements from the GPU in a blocking manner.
+    pub fn download(&self) -> Vec<T> {
+        let buffer = self.get().clone();
+        range::read(&self.gpu, &buffer, 0..self.len)
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.length.upload(&(len as u32));
+    }
+}
+
+impl Gpu {
+    /// Creates a new GPU vec holding the given elements.
+    pub fn create_vec<T, Mut: Mutability>(&self, data: &[T]) -> GpuVec<T, Mut>
+    where
+        T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        self.try_create_vec(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new GPU vec holding the given elements, returning an error if
+    /// wgpu rejects it.
+    pub fn try_create_vec<T, Mut: Mutability>(&self, data: &[T]) -> Result<GpuVec<T, Mut>>
+    where
+        T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        let mut vec = self.try_create_vec_empty(data.len())?;
+        vec.extend(data);
+        Ok(vec)
+    }
+
+    /// Creates a new empty GPU vec with room for `capacity` elements.
+    pub fn create_vec_empty<T, Mut: Mutability>(&self, capacity: usize) -> GpuVec<T, Mut>
+    where
+        T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        self.try_create_vec_empty(capacity)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn try_create_vec_empty<T, Mut: Mutability>(&self, capacity: usize) -> Result<GpuVec<T, Mut>>
+    where
+        T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+    {
+        // Storage bindings can't be empty, so there is always room for one element.
+        let capacity = capacity.max(1);
+        let id = BufferId::new();
+        let buffer = self.error_scope(|| {
+            self.device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: capacity as u64 * stride::<T>(),
+                usage: USAGES,
+                mapped_at_creation: false,
+            })
+        })?;
+
+        self.binding_manager.add_resource(id, buffer);
+        Ok(GpuVec {
+            gpu: self.clone(),
+            buffer: id,
+            len: 0,
+            capacity,
+            length: self.try_create_uniform(&0)?,
+
+            _type: PhantomData,
+            _mut: PhantomData,
+        })
+    }
+}
+
+impl<T, Mut: Mutability> BufferBinding for GpuVec<T, Mut> {
+    fn get_id(&self) -> BufferId {
+        self.buffer
+    }
+}
+
+/// Operations cover the whole capacity and leave the length unchanged.
+impl<T, Mut: Mutability> BufferOps for GpuVec<T, Mut>
+where
+    T: ShaderType + ShaderSize + WriteInto + CreateFrom,
+{
+    type Element = T;
+}
+
+impl<T, Mut: Mutability> Sealed for GpuVec<T, Mut> {
+    fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+}
+
+impl<T> Bindable for GpuVec<T, Mutable> {
+    fn resource_id(&self) -> BindableResourceId {
+        BindableResourceId::Buffer(self.buffer)
+    }
+
+    fn binding_type(&self) -> BindingType {
+        BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+}
+
+impl<T> Bindable for GpuVec<T, Immutable> {
+    fn resource_id(&self) -> BindableResourceId {
+        BindableResourceId::Buffer(self.buffer)
+    }
+
+    fn binding_type(&self) -> BindingType {
+        BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+}
+
+impl<T, Mut: Mutability> Drop for GpuVec<T, Mut> {
+    fn drop(&mut self) {
+        self.gpu.binding_manager.remove_resource(self.buffer);
+    }
+}
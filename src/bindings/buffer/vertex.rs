@@ -1,6 +1,10 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
-use encase::{internal::WriteInto, DynamicStorageBuffer, ShaderSize, ShaderType, StorageBuffer};
+use bytemuck::Pod;
+use encase::{
+    internal::{CreateFrom, WriteInto},
+    DynamicStorageBuffer, ShaderSize, ShaderType, StorageBuffer,
+};
 use parking_lot::MappedRwLockReadGuard;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
@@ -13,9 +17,17 @@ use crate::{
     misc::ids::BufferId,
 };
 
-use super::BufferBinding;
+use super::{ops::sealed::Sealed, pod, range, BufferBinding, BufferOps};
+
+const USAGES: BufferUsages = BufferUsages::COPY_DST
+    .union(BufferUsages::COPY_SRC)
+    .union(BufferUsages::STORAGE)
+    .union(BufferUsages::INDIRECT)
+    .union(BufferUsages::VERTEX);
 
-/// Represents the vertices of a mesh for rendering.
+/// Represents the vertices of a mesh for rendering. Vertex buffers can also be
+/// bound as storage, so a compute shader can generate meshes that are drawn
+/// without any copies.
 pub struct VertexBuffer<T> {
     gpu: Gpu,
     buffer: BufferId,
@@ -29,8 +41,6 @@ impl<T> VertexBuffer<T> {
         })
     }
 
-    // todo: make condensed vertex buffers that just bytemuck cast?
-
     pub fn upload(&self, data: &[T])
     where
         T: ShaderType + ShaderSize + WriteInto,
@@ -38,23 +48,51 @@ impl<T> VertexBuffer<T> {
         let mut buffer = Vec::new();
         let mut storage = StorageBuffer::new(&mut buffer);
         storage.write(&data).unwrap();
+        self.upload_bytes(&buffer);
+    }
+
+    /// Uploads vertices by casting them to bytes, skipping encase. See [`Gpu::create_vertex_pod`].
+    pub fn upload_pod(&self, data: &[T])
+    where
+        T: Pod + ShaderType + ShaderSize + WriteInto,
+    {
+        self.upload_bytes(pod::cast(data));
+    }
 
+    fn upload_bytes(&self, buffer: &[u8]) {
         let this = self.get();
         if buffer.len() as u64 > this.size() {
             drop(this);
             let replacement = self.gpu.device.create_buffer_init(&BufferInitDescriptor {
                 label: None,
-                contents: &buffer,
-                usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
+                contents: buffer,
+                usage: USAGES,
             });
 
             let binding_manager = &self.gpu.binding_manager;
             binding_manager.add_resource(self.buffer, replacement);
             binding_manager.mark_resource_dirty(&BindableResourceId::Buffer(self.buffer));
         } else {
-            self.gpu.queue.write_buffer(&this, 0, &buffer);
+            self.gpu.queue.write_buffer(&this, 0, buffer);
         }
     }
+
+    /// Overwrites the vertices starting at vertex `offset`, without growing the buffer.
+    pub fn upload_range(&self, offset: usize, data: &[T])
+    where
+        T: ShaderType + ShaderSize + WriteInto,
+    {
+        range::write(&self.gpu, &self.get(), offset, data);
+    }
+
+    /// Downloads the vertices in the range, in a blocking manner.
+    pub fn download_range(&self, range: Range<usize>) -> Vec<T>
+    where
+        T: ShaderType + ShaderSize + CreateFrom,
+    {
+        let buffer = self.get().clone();
+        range::read(&self.gpu, &buffer, range)
+    }
 }
 
 impl Gpu {
@@ -65,12 +103,25 @@ impl Gpu {
         let mut buffer = Vec::new();
         let mut storage = DynamicStorageBuffer::new(&mut buffer);
         storage.write(data).unwrap();
+        self.create_vertex_bytes(&buffer)
+    }
 
+    /// Creates a vertex buffer from [`Pod`] vertices, which are cast straight
+    /// to bytes instead of being serialized with encase. The type's layout must
+    /// match WGSL's, see [`Gpu::create_storage_pod`].
+    pub fn create_vertex_pod<T>(&self, data: &[T]) -> VertexBuffer<T>
+    where
+        T: Pod + ShaderType + ShaderSize + WriteInto,
+    {
+        self.create_vertex_bytes(pod::cast(data))
+    }
+
+    fn create_vertex_bytes<T>(&self, contents: &[u8]) -> VertexBuffer<T> {
         let id = BufferId::new();
         let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
             label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
-            contents: &buffer,
+            usage: USAGES,
+            contents,
         });
 
         self.binding_manager.add_resource(id, buffer);
@@ -88,8 +139,8 @@ impl Gpu {
         let id = BufferId::new();
         let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: (std::mem::size_of::<T>() * size) as u64,
-            usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
+            size: size as u64 * range::stride::<T>(),
+            usage: USAGES,
             mapped_at_creation: false,
         });
 
@@ -108,6 +159,16 @@ impl<T> BufferBinding for VertexBuffer<T> {
     }
 }
 
+impl<T: ShaderType + ShaderSize + WriteInto> BufferOps for VertexBuffer<T> {
+    type Element = T;
+}
+
+impl<T> Sealed for VertexBuffer<T> {
+    fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+}
+
 impl<T> Bindable for VertexBuffer<T> {
     fn resource_id(&self) -> BindableResourceId {
         BindableResourceId::Buffer(self.buffer)
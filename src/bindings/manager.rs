@@ -1,24 +1,35 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
 use wgpu::{
-    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, Device,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, BindingType,
+    Buffer, BufferBinding, Device,
 };
 
 use crate::{
-    misc::ids::{PipelineId, TextureCollectionId, TextureId},
-    pipeline::PipelineStatus,
+    misc::ids::{BufferId, PipelineId, TextureCollectionId, TextureId},
+    pipeline::{BindingEntry, PipelineStatus},
 };
 
 use super::{BindableResource, BindableResourceId};
 
 type RwMap<K, V> = RwLock<HashMap<K, V>>;
 
+/// How many bind groups each pipeline keeps per group, so swapping a
+/// [`crate::bindings::buffer::PingPong`] back and forth reuses them.
+const CACHED_BIND_GROUPS: usize = 2;
+
 // todo: reference count resources
 pub struct BindingManager {
     pipelines: RwMap<PipelineId, PipelineStatus>,
     resources: RwMap<BindableResourceId, BindableResource>,
     collections: RwMap<TextureCollectionId, Vec<TextureId>>,
+    /// Changes whenever the resource behind an ID is replaced.
+    generations: RwMap<BindableResourceId, u64>,
+    next_generation: AtomicU64,
 }
 
 impl BindingManager {
@@ -27,29 +38,104 @@ impl BindingManager {
             pipelines: RwLock::new(HashMap::new()),
             resources: RwLock::new(HashMap::new()),
             collections: RwLock::new(HashMap::new()),
+            generations: RwLock::new(HashMap::new()),
+            next_generation: AtomicU64::new(0),
         }
     }
 
+    /// Marks the bind groups using the resource as dirty. Their cached bind
+    /// groups that reference a replaced resource are dropped so they don't keep
+    /// it alive, while swapped buffers keep both of their bind groups.
     pub(crate) fn mark_resource_dirty(&self, resource: &BindableResourceId) {
+        let live = self
+            .generations
+            .read()
+            .values()
+            .copied()
+            .collect::<HashSet<_>>();
+        let mut pipelines = self.pipelines.write();
+        for (
+            _id,
+            PipelineStatus {
+                resources,
+                dirty,
+                cache,
+            },
+        ) in pipelines.iter_mut()
+        {
+            for ((resources, dirty), cache) in resources.iter().zip(dirty).zip(cache) {
+                if resources.contains(resource) {
+                    *dirty = true;
+                    retain_live(cache, &live);
+                }
+            }
+        }
+    }
+
+    /// Returns the bind group of a pipeline, reusing a cached one if it was
+    /// created for the same resources.
+    pub(crate) fn bind_group(
+        &self,
+        device: &Device,
+        id: PipelineId,
+        layout: &BindGroupLayout,
+        entries: &[BindingEntry],
+        group: u32,
+    ) -> BindGroup {
+        let key = self.generation_key(entries, group);
+        let cached = self.pipelines.read()[&id].cache[group as usize]
+            .iter()
+            .find(|(x, _)| *x == key)
+            .map(|(_, bind_group)| bind_group.clone());
+        if let Some(bind_group) = cached {
+            return bind_group;
+        }
+
+        let bind_group = self.create_bind_group(device, layout, entries, group);
         let mut pipelines = self.pipelines.write();
-        for (_id, PipelineStatus { resources, dirty }) in pipelines.iter_mut() {
-            *dirty |= resources.contains(resource);
+        let cache = &mut pipelines.get_mut(&id).unwrap().cache[group as usize];
+        if cache.len() >= CACHED_BIND_GROUPS {
+            cache.remove(0);
         }
+        cache.push((key, bind_group.clone()));
+        bind_group
+    }
+
+    fn generation_key(&self, entries: &[BindingEntry], group: u32) -> Vec<u64> {
+        let generations = self.generations.read();
+        let collections = self.collections.read();
+        let generation = |id: &BindableResourceId| generations.get(id).copied().unwrap_or_default();
+
+        let mut key = Vec::new();
+        for entry in entries.iter().filter(|x| x.group == group) {
+            match &entry.resource {
+                BindableResourceId::TextureCollection(id) => {
+                    key.extend(collections[id].iter().map(|&x| generation(&x.into())))
+                }
+                x => key.push(generation(x)),
+            }
+        }
+        key
     }
 
     pub(crate) fn create_bind_group(
         &self,
         device: &Device,
         layout: &BindGroupLayout,
-        entries: &[BindableResourceId],
+        entries: &[BindingEntry],
+        group: u32,
     ) -> BindGroup {
+        let entries = entries
+            .iter()
+            .filter(|x| x.group == group)
+            .collect::<Vec<_>>();
         let resources = self.resources.read();
         let collections = self.collections.read();
 
         let mut collection_id = 0;
         let collections = entries
             .iter()
-            .filter_map(|x| match x {
+            .filter_map(|x| match &x.resource {
                 BindableResourceId::TextureCollection(id) => {
                     let collection = collections[id]
                         .iter()
@@ -63,16 +149,27 @@ impl BindingManager {
 
         let entries = &entries
             .iter()
-            .enumerate()
-            .map(|(binding, id)| BindGroupEntry {
-                binding: binding as u32,
-                resource: match id {
+            .map(|entry| BindGroupEntry {
+                binding: entry.layout.binding,
+                resource: match &entry.resource {
                     BindableResourceId::TextureCollection(_) => {
                         collection_id += 1;
                         BindingResource::TextureViewArray(&collections[collection_id - 1])
                     }
                     x => match &resources[x] {
-                        BindableResource::Buffer(buffer) => buffer.as_entire_binding(),
+                        BindableResource::Buffer(buffer) => match entry.layout.ty {
+                            // Dynamic buffers bind a single element, which the offset moves through.
+                            BindingType::Buffer {
+                                has_dynamic_offset: true,
+                                min_binding_size: size @ Some(_),
+                                ..
+                            } => BindingResource::Buffer(BufferBinding {
+                                buffer,
+                                offset: 0,
+                                size,
+                            }),
+                            _ => buffer.as_entire_binding(),
+                        },
                         BindableResource::Texture(texture_view) => {
                             BindingResource::TextureView(texture_view)
                         }
@@ -93,13 +190,30 @@ impl BindingManager {
     }
 }
 
+/// Keeps the cached entries whose key only has generations that still belong to a resource.
+fn retain_live<T>(cache: &mut Vec<(Vec<u64>, T)>, live: &HashSet<u64>) {
+    cache.retain(|(key, _)| key.iter().all(|x| live.contains(x)));
+}
+
 impl BindingManager {
     pub(crate) fn add_pipeline(&self, id: PipelineId, status: PipelineStatus) {
         self.pipelines.write().insert(id, status);
     }
 
-    pub(crate) fn get_pipeline(&self, id: PipelineId) -> MappedRwLockReadGuard<'_, PipelineStatus> {
-        RwLockReadGuard::map(self.pipelines.read(), |x| &x[&id])
+    /// Returns the bind groups of a pipeline that need to be recreated, marking them as clean.
+    pub(crate) fn take_dirty_groups(&self, id: PipelineId) -> Vec<u32> {
+        let mut pipelines = self.pipelines.write();
+        let status = pipelines.get_mut(&id).unwrap();
+
+        let dirty = status
+            .dirty
+            .iter()
+            .enumerate()
+            .filter(|(_, dirty)| **dirty)
+            .map(|(group, _)| group as u32)
+            .collect();
+        status.dirty.fill(false);
+        dirty
     }
 
     pub(crate) fn remove_pipeline(&self, id: PipelineId) {
@@ -111,7 +225,29 @@ impl BindingManager {
         id: impl Into<BindableResourceId>,
         resource: impl Into<BindableResource>,
     ) {
-        self.resources.write().insert(id.into(), resource.into());
+        let id = id.into();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        self.generations.write().insert(id, generation);
+        self.resources.write().insert(id, resource.into());
+    }
+
+    /// Swaps the buffers behind two IDs, marking everything bound to them dirty.
+    pub(crate) fn swap_buffers(&self, a: BufferId, b: BufferId) {
+        let (a, b) = (BindableResourceId::Buffer(a), BindableResourceId::Buffer(b));
+        {
+            let mut resources = self.resources.write();
+            let resource = resources.remove(&a).unwrap();
+            let resource = resources.insert(b, resource).unwrap();
+            resources.insert(a, resource);
+
+            let mut generations = self.generations.write();
+            let (x, y) = (generations[&a], generations[&b]);
+            generations.insert(a, y);
+            generations.insert(b, x);
+        }
+
+        self.mark_resource_dirty(&a);
+        self.mark_resource_dirty(&b);
     }
 
     pub(crate) fn get_resource(
@@ -121,8 +257,21 @@ impl BindingManager {
         RwLockReadGuard::map(self.resources.read(), |x| &x[&id.into()])
     }
 
+    /// Clones the handles of several buffers under a single read lock, so
+    /// commands can be encoded without holding it.
+    pub(crate) fn get_buffers<const N: usize>(&self, ids: [BufferId; N]) -> [Buffer; N] {
+        let resources = self.resources.read();
+        ids.map(|id| {
+            resources[&BindableResourceId::Buffer(id)]
+                .expect_buffer()
+                .clone()
+        })
+    }
+
     pub(crate) fn remove_resource(&self, id: impl Into<BindableResourceId>) {
-        self.resources.write().remove(&id.into());
+        let id = id.into();
+        self.generations.write().remove(&id);
+        self.resources.write().remove(&id);
     }
 
     pub(crate) fn add_collection(&self, id: TextureCollectionId, resources: Vec<TextureId>) {
@@ -146,3 +295,66 @@ impl Default for BindingManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use wgpu::{BindGroupLayoutDescriptor, ShaderStages};
+
+    use crate::{
+        gpu::Gpu,
+        misc::ids::PipelineId,
+        pipeline::{BindingEntry, PipelineStatus},
+    };
+
+    use super::retain_live;
+
+    #[test]
+    fn retain_live_drops_replaced_generations() {
+        let mut cache = vec![(vec![0, 1], 'a'), (vec![1, 0], 'b'), (vec![2, 1], 'c')];
+        retain_live(&mut cache, &HashSet::from([0, 1, 3]));
+        assert_eq!(cache, [(vec![0, 1], 'a'), (vec![1, 0], 'b')]);
+    }
+
+    #[test]
+    fn ping_pong_cache() {
+        let Ok(gpu) = Gpu::new() else {
+            return;
+        };
+        let cells = gpu.create_ping_pong(&vec![0u32; 4]);
+
+        let entries = [
+            BindingEntry::new(cells.read(), 0, 0, ShaderStages::COMPUTE),
+            BindingEntry::new(cells.write(), 0, 1, ShaderStages::COMPUTE),
+        ];
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &entries.each_ref().map(|x| x.layout),
+            });
+
+        let manager = &gpu.binding_manager;
+        let id = PipelineId::new();
+        manager.add_pipeline(id, PipelineStatus::new(&entries));
+        let bind_group = || manager.bind_group(&gpu.device, id, &layout, &entries, 0);
+        let cached = || manager.pipelines.read()[&id].cache[0].len();
+
+        let first = bind_group();
+        cells.swap();
+        let second = bind_group();
+        cells.swap();
+        assert!(first == bind_group());
+        cells.swap();
+        assert!(second == bind_group());
+        assert!(first != second);
+        assert_eq!(cached(), 2);
+
+        // Growing the buffers replaces them, so neither cached bind group is valid anymore.
+        cells.upload(&vec![0; 8]);
+        assert_eq!(cached(), 0);
+        bind_group();
+        assert_eq!(cached(), 1);
+    }
+}
@@ -5,12 +5,18 @@ use std::marker::PhantomData;
 use format::TextureFormat;
 use nalgebra::{Vector2, Vector3};
 use wgpu::{
-    BindingType, Extent3d, Origin3d, TexelCopyBufferInfo, TexelCopyBufferLayout,
-    TexelCopyTextureInfo, TextureAspect, TextureDescriptor, TextureDimension, TextureSampleType,
-    TextureUsages, TextureViewDescriptor, TextureViewDimension,
+    BindingType, Buffer, CommandEncoder, Extent3d, Origin3d, TexelCopyBufferInfo,
+    TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, COPY_BYTES_PER_ROW_ALIGNMENT,
 };
 
-use crate::{gpu::Gpu, misc::ids::TextureId};
+use crate::{
+    error::Result,
+    gpu::Gpu,
+    misc::{ids::TextureId, staging::Readback},
+    submission::Submission,
+};
 
 use super::{buffer::BufferBinding, Bindable, BindableResourceId};
 
@@ -52,67 +58,104 @@ impl<Format: TextureFormat> Texture<Format> {
         );
     }
 
-    pub fn copy_to_buffer<T: BufferBinding>(&self, buffer: &T) {
-        let buffer = self.gpu.binding_manager.get_resource(buffer.get_id());
-        let buffer = buffer.expect_buffer();
-
-        self.gpu.immediate_dispatch(|encoder| {
-            encoder.copy_texture_to_buffer(
-                TexelCopyTextureInfo {
-                    texture: &self.texture,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                    aspect: TextureAspect::All,
-                },
-                TexelCopyBufferInfo {
-                    buffer,
-                    layout: TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(self.size.x * 4),
-                        rows_per_image: Some(self.size.y),
-                    },
-                },
-                Extent3d {
-                    width: self.size.x,
-                    height: self.size.y,
-                    depth_or_array_layers: self.size.z,
+    pub fn copy_to_buffer<T: BufferBinding>(&self, buffer: &T) -> Submission {
+        self.gpu
+            .immediate_dispatch(|encoder| self.encode_copy_to_buffer(encoder, buffer))
+    }
+
+    /// Encodes a copy of the whole texture into the buffer.
+    pub(crate) fn encode_copy_to_buffer<T: BufferBinding>(
+        &self,
+        encoder: &mut CommandEncoder,
+        buffer: &T,
+    ) {
+        let [buffer] = self.gpu.binding_manager.get_buffers([buffer.get_id()]);
+        self.encode_copy(encoder, &buffer, self.size.x * 4);
+    }
+
+    /// Starts reading the texture back. Rows are padded to wgpu's copy alignment
+    /// in the staging buffer, and the padding is stripped once the data arrives.
+    pub(crate) fn readback(&self) -> Readback {
+        let row = self.size.x * 4;
+        let padded = row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let rows = self.size.y * self.size.z;
+
+        Readback::new(
+            &self.gpu,
+            padded as u64 * rows as u64,
+            |encoder, staging| self.encode_copy(encoder, staging, padded),
+        )
+        .unpad_rows(row as usize, padded as usize)
+    }
+
+    fn encode_copy(&self, encoder: &mut CommandEncoder, buffer: &Buffer, bytes_per_row: u32) {
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(self.size.y),
                 },
-            );
-        });
+            },
+            Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: self.size.z,
+            },
+        );
     }
 }
 
 impl Gpu {
     pub fn create_texture_2d<Format: TextureFormat>(&self, size: Vector2<u32>) -> Texture<Format> {
-        let texture = self.device.create_texture(&TextureDescriptor {
-            label: None,
-            size: Extent3d {
-                width: size.x,
-                height: size.y,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: Format::as_format(),
-            usage: TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST
-                | TextureUsages::RENDER_ATTACHMENT
-                | TextureUsages::COPY_SRC,
-            view_formats: &[],
-        });
+        self.try_create_texture_2d(size)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        let id = TextureId::new();
-        let view = texture.create_view(&TextureViewDescriptor::default());
+    /// Creates a new 2D texture, returning an error if wgpu rejects it (for
+    /// example, if it is larger than the max texture dimension).
+    pub fn try_create_texture_2d<Format: TextureFormat>(
+        &self,
+        size: Vector2<u32>,
+    ) -> Result<Texture<Format>> {
+        let (texture, view) = self.error_scope(|| {
+            let texture = self.device.create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: Format::as_format(),
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        })?;
 
+        let id = TextureId::new();
         self.binding_manager.add_resource(id, view);
-        Texture {
+        Ok(Texture {
             gpu: self.clone(),
             id,
             texture,
             size: Vector3::new(size.x, size.y, 1),
             _format: PhantomData,
-        }
+        })
     }
 }
 
@@ -0,0 +1,67 @@
+//! Errors returned by the fallible (`try_*`) versions of tufa operations.
+
+use std::{
+    error,
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use crate::shader::{BindingError, ShaderDiagnostic};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Wgpu rejected a resource, shader, or pipeline as invalid.
+    Validation(String),
+    /// The device ran out of memory while creating a resource.
+    OutOfMemory,
+    /// Any other error reported by wgpu.
+    Internal(String),
+    /// A shader failed to parse or validate.
+    Shader(ShaderDiagnostic),
+    /// A resource bound to a pipeline doesn't match how the shader declares it.
+    Binding(BindingError),
+    /// A shader file could not be read.
+    Io(PathBuf, Arc<io::Error>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Validation(description) | Error::Internal(description) => {
+                f.write_str(description)
+            }
+            Error::OutOfMemory => f.write_str("Out of memory"),
+            Error::Shader(diagnostic) => diagnostic.fmt(f),
+            Error::Binding(error) => error.fmt(f),
+            Error::Io(path, err) => write!(f, "Failed to read `{}`: {err}", path.display()),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<wgpu::Error> for Error {
+    fn from(value: wgpu::Error) -> Self {
+        match value {
+            wgpu::Error::Validation { description, .. } => Error::Validation(description),
+            wgpu::Error::OutOfMemory { .. } => Error::OutOfMemory,
+            wgpu::Error::Internal { description, .. } => Error::Internal(description),
+        }
+    }
+}
+
+impl From<ShaderDiagnostic> for Error {
+    fn from(value: ShaderDiagnostic) -> Self {
+        Error::Shader(value)
+    }
+}
+
+impl From<BindingError> for Error {
+    fn from(value: BindingError) -> Self {
+        Error::Binding(value)
+    }
+}
@@ -2,21 +2,27 @@
 
 use std::{mem, ops::Deref, sync::Arc};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use parking_lot::Mutex;
 use wgpu::{
-    AdapterInfo, CommandBuffer, CommandEncoder, CommandEncoderDescriptor, Device, DeviceDescriptor,
-    Features, Instance, InstanceDescriptor, Limits, MaintainBase, PowerPreference, Queue,
-    RequestAdapterOptions,
+    Adapter, AdapterInfo, Backends, CommandBuffer, CommandEncoder, CommandEncoderDescriptor,
+    Device, DeviceDescriptor, ErrorFilter, Features, Instance, InstanceDescriptor, Limits,
+    MaintainBase, PowerPreference, Queue, RequestAdapterOptions,
 };
 
 use crate::{
+    adapter::AdapterDescription,
     bindings::{
         buffer::{IndexBuffer, VertexBuffer},
         manager::BindingManager,
     },
-    misc::default_buffer::DefaultBuffers,
+    error,
+    misc::{
+        default_buffer::DefaultBuffers, fill::FillKernel, poll_driver::PollDriver,
+        staging::StagingPool,
+    },
     pipeline::render::Vertex,
+    submission::Submission,
 };
 
 #[derive(Clone)]
@@ -34,12 +40,29 @@ pub struct GpuInner {
     pub(crate) binding_manager: BindingManager,
     default_buffers: DefaultBuffers,
     dispatch_queue: Mutex<DispatchQueue>,
+    pub(crate) poll_driver: Arc<PollDriver>,
+    pub(crate) staging_pool: StagingPool,
+    pub(crate) fill_kernel: FillKernel,
 }
 
 pub struct GpuBuilder {
     limits: Limits,
     features: Features,
     power_preference: PowerPreference,
+
+    backends: Backends,
+    force_fallback_adapter: bool,
+    adapter: AdapterSelection,
+}
+
+/// How [`GpuBuilder::build`] picks an adapter.
+enum AdapterSelection {
+    /// Let wgpu choose based on the power preference.
+    Default,
+    /// The first adapter whose name contains the string (case-insensitive).
+    Name(String),
+    /// The adapter at this index in [`GpuBuilder::adapters`].
+    Index(usize),
 }
 
 #[derive(Default)]
@@ -67,6 +90,37 @@ impl GpuBuilder {
         }
     }
 
+    /// Limits the backends (Vulkan, Metal, DX12, GL, etc.) that adapters can be picked from.
+    pub fn backends(self, backends: Backends) -> Self {
+        Self { backends, ..self }
+    }
+
+    /// Forces the use of a fallback (software) adapter, like llvmpipe or lavapipe.
+    /// Useful for running on machines without a GPU.
+    /// Can't be combined with picking an adapter by name or index.
+    pub fn force_fallback_adapter(self) -> Self {
+        Self {
+            force_fallback_adapter: true,
+            ..self
+        }
+    }
+
+    /// Selects the first adapter whose name contains `name`, ignoring case.
+    pub fn adapter_name(self, name: impl Into<String>) -> Self {
+        Self {
+            adapter: AdapterSelection::Name(name.into()),
+            ..self
+        }
+    }
+
+    /// Selects the adapter at `index` in the list returned by [`GpuBuilder::adapters`].
+    pub fn adapter_index(self, index: usize) -> Self {
+        Self {
+            adapter: AdapterSelection::Index(index),
+            ..self
+        }
+    }
+
     pub fn with_raytracing(self) -> Self {
         self.with_features(
             Features::EXPERIMENTAL_RAY_TRACING_ACCELERATION_STRUCTURE
@@ -74,15 +128,55 @@ impl GpuBuilder {
         )
     }
 
+    /// Enables push constants of up to `size` bytes, which can then be pushed
+    /// with the `*_push` dispatch and draw functions of pipelines.
+    pub fn with_push_constants(self, size: u32) -> Self {
+        let limits = Limits {
+            max_push_constant_size: self.limits.max_push_constant_size.max(size),
+            ..self.limits
+        };
+        Self { limits, ..self }.with_features(Features::PUSH_CONSTANTS)
+    }
+
+    /// Enables drawing several indirect entries with a single command in the
+    /// `multi_draw_*indirect` functions of render pipelines.
+    pub fn with_multi_draw_indirect(self) -> Self {
+        self.with_features(Features::MULTI_DRAW_INDIRECT)
+    }
+
+    /// Lists all adapters available with the selected backends.
+    pub fn adapters(&self) -> Vec<AdapterDescription> {
+        let instance = self.instance();
+        instance
+            .enumerate_adapters(self.backends)
+            .iter()
+            .map(AdapterDescription::new)
+            .collect()
+    }
+
     pub fn build(self) -> Result<Gpu> {
-        let instance = Instance::new(&InstanceDescriptor::default());
-        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
-            power_preference: self.power_preference,
-            ..Default::default()
-        }))
-        .context("Error requesting adapter")?;
+        let instance = self.instance();
+        let adapter = self.request_adapter(&instance)?;
         let info = adapter.get_info();
 
+        let description = AdapterDescription::new(&adapter);
+        if !description.supports(self.features) {
+            bail!(
+                "Adapter `{}` is missing required features: {:?}",
+                info.name,
+                self.features - description.features
+            );
+        }
+
+        let limits = description.unsupported_limits(&self.limits);
+        if !limits.is_empty() {
+            bail!(
+                "Adapter `{}` does not support the required limits: {}",
+                info.name,
+                limits.join(", ")
+            );
+        }
+
         let (device, queue) = pollster::block_on(adapter.request_device(&DeviceDescriptor {
             required_limits: self.limits,
             required_features: self.features,
@@ -100,9 +194,46 @@ impl GpuBuilder {
                 binding_manager: BindingManager::new(),
                 default_buffers: DefaultBuffers::empty(),
                 dispatch_queue: Mutex::new(DispatchQueue::default()),
+                poll_driver: Arc::new(PollDriver::new()),
+                staging_pool: StagingPool::default(),
+                fill_kernel: FillKernel::default(),
             }),
         })
     }
+
+    fn instance(&self) -> Instance {
+        Instance::new(&InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    fn request_adapter(&self, instance: &Instance) -> Result<Adapter> {
+        if self.force_fallback_adapter && !matches!(self.adapter, AdapterSelection::Default) {
+            bail!("A fallback adapter can't be forced when picking an adapter by name or index");
+        }
+
+        let adapters = || instance.enumerate_adapters(self.backends).into_iter();
+        match &self.adapter {
+            AdapterSelection::Default => {
+                pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    force_fallback_adapter: self.force_fallback_adapter,
+                    compatible_surface: None,
+                }))
+                .context("Error requesting adapter")
+            }
+            AdapterSelection::Name(name) => {
+                let query = name.to_lowercase();
+                adapters()
+                    .find(|x| x.get_info().name.to_lowercase().contains(&query))
+                    .with_context(|| format!("No adapter found matching `{name}`"))
+            }
+            AdapterSelection::Index(index) => adapters()
+                .nth(*index)
+                .with_context(|| format!("No adapter found at index {index}")),
+        }
+    }
 }
 
 impl Gpu {
@@ -111,6 +242,10 @@ impl Gpu {
             limits: Limits::default(),
             features: Features::VERTEX_WRITABLE_STORAGE,
             power_preference: PowerPreference::None,
+
+            backends: Backends::all(),
+            force_fallback_adapter: false,
+            adapter: AdapterSelection::Default,
         }
     }
 
@@ -123,6 +258,16 @@ impl Gpu {
         &self.info
     }
 
+    /// Returns the features enabled on the device
+    pub fn features(&self) -> Features {
+        self.device.features()
+    }
+
+    /// Returns the limits enabled on the device
+    pub fn limits(&self) -> Limits {
+        self.device.limits()
+    }
+
     /// Processes any resource cleanups and mapping callbacks
     pub fn poll(&self) {
         let _ = self.device.poll(MaintainBase::Poll).unwrap();
@@ -143,17 +288,50 @@ impl Gpu {
     pub(crate) fn default_buffers(&self) -> &(VertexBuffer<Vertex>, IndexBuffer) {
         self.default_buffers.get(self)
     }
+
+    /// Runs `proc`, capturing any wgpu errors it causes instead of panicking.
+    pub(crate) fn error_scope<T>(&self, proc: impl FnOnce() -> T) -> error::Result<T> {
+        let filters = [
+            ErrorFilter::Validation,
+            ErrorFilter::OutOfMemory,
+            ErrorFilter::Internal,
+        ];
+        for filter in filters {
+            self.device.push_error_scope(filter);
+        }
+
+        let out = proc();
+
+        let mut error = None;
+        for _ in filters {
+            error = error.or(pollster::block_on(self.device.pop_error_scope()));
+        }
+
+        match error {
+            Some(error) => Err(error.into()),
+            None => Ok(out),
+        }
+    }
 }
 
 impl Gpu {
-    pub fn flush_dispatch_queue(&self) {
+    /// Submits every queued dispatch, returning a handle to wait on them.
+    pub fn flush_dispatch_queue(&self) -> Submission {
+        self.submit_dispatch_queue(None)
+    }
+
+    /// Submits the queued command buffers followed by `last`, then registers the queued callbacks.
+    pub(crate) fn submit_dispatch_queue(&self, last: Option<CommandBuffer>) -> Submission {
         let queue = mem::take(&mut *self.dispatch_queue.lock());
 
-        self.queue.submit(queue.command_buffers);
+        let command_buffers = queue.command_buffers.into_iter().chain(last);
+        let index = self.queue.submit(command_buffers);
 
         for callback in queue.callbacks.into_iter() {
             self.queue.on_submitted_work_done(callback);
         }
+
+        Submission::new(self, index)
     }
 
     pub(crate) fn queue_dispatch(&self, proc: impl FnOnce(&mut CommandEncoder)) {
@@ -177,43 +355,18 @@ impl Gpu {
         queue.callbacks.push(Box::new(callback));
     }
 
-    pub(crate) fn immediate_dispatch(&self, proc: impl FnOnce(&mut CommandEncoder)) {
+    pub(crate) fn immediate_dispatch(&self, proc: impl FnOnce(&mut CommandEncoder)) -> Submission {
         self.queue_dispatch(proc);
-        self.flush_dispatch_queue();
+        self.flush_dispatch_queue()
     }
 
     pub(crate) fn immediate_dispatch_callback(
         &self,
         proc: impl FnOnce(&mut CommandEncoder),
         callback: impl FnOnce() + Send + 'static,
-    ) {
-        self.queue_dispatch(proc);
-        let mut queue = self.dispatch_queue.lock();
-        queue.callbacks.push(Box::new(callback));
-        drop(queue);
-
-        self.flush_dispatch_queue();
-    }
-
-    pub(crate) fn dispach(&self, proc: impl FnOnce(&mut CommandEncoder), immediate: bool) {
-        if immediate {
-            self.immediate_dispatch(proc);
-        } else {
-            self.queue_dispatch(proc);
-        }
-    }
-
-    pub(crate) fn dispach_callback(
-        &self,
-        proc: impl FnOnce(&mut CommandEncoder),
-        callback: impl FnOnce() + Send + 'static,
-        immediate: bool,
-    ) {
-        if immediate {
-            self.immediate_dispatch_callback(proc, callback);
-        } else {
-            self.queue_dispatch_callback(proc, callback);
-        }
+    ) -> Submission {
+        self.queue_dispatch_callback(proc, callback);
+        self.flush_dispatch_queue()
     }
 }
 
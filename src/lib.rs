@@ -2,12 +2,19 @@
 
 use wgpu::TextureFormat;
 
+pub mod adapter;
+pub mod batch;
 pub mod bindings;
+pub mod error;
 pub mod gpu;
 #[cfg(feature = "interactive")]
 pub mod interactive;
 pub mod misc;
 pub mod pipeline;
+pub mod shader;
+pub mod submission;
+
+pub use error::Error;
 
 pub mod export {
     //! Exported types from crates tufa uses internally.
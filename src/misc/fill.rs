@@ -0,0 +1,125 @@
+use std::{num::NonZeroU64, sync::OnceLock};
+
+use parking_lot::Mutex;
+use wgpu::{
+    include_wgsl,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferBinding, BufferUsages,
+    CommandEncoder, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device,
+};
+
+/// The most workgroups a dispatch can have along one axis.
+const MAX_WORKGROUPS: u32 = 65535;
+const WORKGROUP_SIZE: u32 = 64;
+/// How many recently used patterns keep their buffer around.
+const MAX_PATTERNS: usize = 8;
+
+/// A compute kernel that repeats a pattern of words over a storage buffer.
+/// The pipeline is only created the first time it is needed.
+#[derive(Default)]
+pub(crate) struct FillKernel {
+    pipeline: OnceLock<ComputePipeline>,
+    /// Pattern buffers are never written after creation, so queued fills can
+    /// share them without racing each other.
+    patterns: Mutex<Vec<(Vec<u8>, Buffer)>>,
+}
+
+impl FillKernel {
+    /// Encodes a fill of the whole buffer, with the pattern starting at its start.
+    /// Buffers larger than a storage binding allows are filled in several chunks.
+    pub fn encode(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        buffer: &Buffer,
+        pattern: &[u8],
+    ) {
+        if buffer.size() == 0 {
+            return;
+        }
+
+        let pipeline = self.pipeline.get_or_init(|| {
+            let module = device.create_shader_module(include_wgsl!("fill.wgsl"));
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("tufa fill"),
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        });
+        let pattern = self.pattern(device, pattern);
+
+        // Chunks start at aligned offsets and on a pattern boundary, so the
+        // pattern lines up with the start of every chunk.
+        let limits = device.limits();
+        let alignment = lcm(
+            limits.min_storage_buffer_offset_alignment as u64,
+            pattern.size(),
+        );
+        let chunk = limits.max_storage_buffer_binding_size as u64 / alignment * alignment;
+        assert!(chunk > 0, "Fill pattern is too large to bind");
+
+        let layout = pipeline.get_bind_group_layout(0);
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+        pass.set_pipeline(pipeline);
+        for offset in (0..buffer.size()).step_by(chunk as usize) {
+            let size = chunk.min(buffer.size() - offset);
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(BufferBinding {
+                            buffer,
+                            offset,
+                            size: NonZeroU64::new(size),
+                        }),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: pattern.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let groups = ((size / 4) as u32).div_ceil(WORKGROUP_SIZE);
+            pass.set_bind_group(0, Some(&bind_group), &[]);
+            pass.dispatch_workgroups(
+                groups.min(MAX_WORKGROUPS),
+                groups.div_ceil(MAX_WORKGROUPS),
+                1,
+            );
+        }
+    }
+
+    /// Returns the buffer holding the pattern, reusing it if it was used recently.
+    fn pattern(&self, device: &Device, pattern: &[u8]) -> Buffer {
+        let mut patterns = self.patterns.lock();
+        if let Some(index) = patterns.iter().position(|(x, _)| x == pattern) {
+            let entry = patterns.remove(index);
+            patterns.push(entry);
+        } else {
+            if patterns.len() >= MAX_PATTERNS {
+                patterns.remove(0);
+            }
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: pattern,
+                usage: BufferUsages::STORAGE,
+            });
+            patterns.push((pattern.to_vec(), buffer));
+        }
+        patterns.last().unwrap().1.clone()
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
@@ -0,0 +1,16 @@
+@group(0) @binding(0) var<storage, read_write> buffer: array<u32>;
+@group(0) @binding(1) var<storage, read> pattern: array<u32>;
+
+@compute
+@workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let i = id.x + id.y * groups.x * 64u;
+    if i >= arrayLength(&buffer) {
+        return;
+    }
+
+    buffer[i] = pattern[i % arrayLength(&pattern)];
+}
@@ -2,5 +2,8 @@
 
 pub mod camera;
 pub(crate) mod default_buffer;
+pub(crate) mod fill;
 pub(crate) mod ids;
-pub(crate) mod thread_ptr;
+pub(crate) mod poll_driver;
+pub(crate) mod signal;
+pub(crate) mod staging;
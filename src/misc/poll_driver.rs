@@ -0,0 +1,90 @@
+use std::{
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use parking_lot::{Condvar, Mutex};
+use wgpu::{Device, MaintainBase};
+
+/// Polls the device on a background thread while any futures are waiting on
+/// it, so they complete without the caller having to call [`crate::gpu::Gpu::poll`].
+/// The thread is only started once the first future needs it.
+pub(crate) struct PollDriver {
+    state: Arc<State>,
+    thread: OnceLock<()>,
+}
+
+/// Keeps the driver polling until it is dropped, usually from inside a wgpu callback.
+pub(crate) struct PollGuard {
+    state: Arc<State>,
+}
+
+struct State {
+    /// The number of live guards, or `None` once the driver is shut down.
+    pending: Mutex<Option<usize>>,
+    condvar: Condvar,
+}
+
+impl PollDriver {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(State {
+                pending: Mutex::new(Some(0)),
+                condvar: Condvar::new(),
+            }),
+            thread: OnceLock::new(),
+        }
+    }
+
+    pub fn request(&self, device: &Device) -> PollGuard {
+        self.thread.get_or_init(|| {
+            let (state, device) = (self.state.clone(), device.clone());
+            thread::Builder::new()
+                .name("tufa-poll".into())
+                .spawn(move || state.run(&device))
+                .unwrap();
+        });
+
+        if let Some(pending) = self.state.pending.lock().as_mut() {
+            *pending += 1;
+        }
+        self.state.condvar.notify_one();
+
+        PollGuard {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl State {
+    fn run(&self, device: &Device) {
+        loop {
+            let mut pending = self.pending.lock();
+            while *pending == Some(0) {
+                self.condvar.wait(&mut pending);
+            }
+
+            if pending.is_none() {
+                return;
+            }
+
+            drop(pending);
+            let _ = device.poll(MaintainBase::Wait);
+        }
+    }
+}
+
+impl Drop for PollGuard {
+    fn drop(&mut self) {
+        if let Some(pending) = self.state.pending.lock().as_mut() {
+            *pending -= 1;
+        }
+    }
+}
+
+impl Drop for PollDriver {
+    fn drop(&mut self) {
+        *self.state.pending.lock() = None;
+        self.state.condvar.notify_one();
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use parking_lot::{Condvar, Mutex};
+
+/// A value sent from a wgpu callback to whoever is waiting on it, either by
+/// checking it, blocking on it or by awaiting a [`SignalFuture`].
+pub(crate) struct Signal<T> {
+    state: Arc<Mutex<State<T>>>,
+    set: Arc<Condvar>,
+}
+
+/// Resolves to the value of a [`Signal`] once it is set.
+pub(crate) struct SignalFuture<T> {
+    signal: Signal<T>,
+}
+
+struct State<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Signal<T> {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                value: None,
+                waker: None,
+            })),
+            set: Arc::new(Condvar::new()),
+        }
+    }
+
+    pub fn set(&self, value: T) {
+        let mut state = self.state.lock();
+        state.value = Some(value);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.set.notify_all();
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state.lock().value.is_some()
+    }
+
+    pub fn take(&self) -> Option<T> {
+        self.state.lock().value.take()
+    }
+
+    /// Blocks until the value is set, without taking it. Something else has
+    /// to poll the device for the callback setting it to run.
+    pub fn wait(&self) {
+        let mut state = self.state.lock();
+        while state.value.is_none() {
+            self.set.wait(&mut state);
+        }
+    }
+
+    /// Returns ready once the value is set, without taking it.
+    pub fn poll_set(&self, cx: &mut Context) -> Poll<()> {
+        let mut state = self.state.lock();
+        if state.value.is_some() {
+            return Poll::Ready(());
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    pub fn future(&self) -> SignalFuture<T> {
+        SignalFuture {
+            signal: self.clone(),
+        }
+    }
+}
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            set: self.set.clone(),
+        }
+    }
+}
+
+impl<T> Future for SignalFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let mut state = self.signal.state.lock();
+        match state.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use parking_lot::Mutex;
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, MaintainBase,
+    MapMode, COPY_BUFFER_ALIGNMENT,
+};
+
+use crate::{gpu::Gpu, misc::signal::Signal, submission::Submission};
+
+/// The most unused staging buffers kept around for each size.
+const MAX_PER_BUCKET: usize = 4;
+
+/// Staging buffers larger than this aren't pooled, so big one-off downloads
+/// don't keep their memory around.
+const MAX_POOLED_SIZE: u64 = 64 << 20;
+
+/// Recycles `MAP_READ` staging buffers between downloads. Buffers are grouped
+/// into power-of-two size buckets so similar downloads can share them.
+#[derive(Clone, Default)]
+pub(crate) struct StagingPool {
+    buckets: Arc<Mutex<HashMap<u64, Vec<Buffer>>>>,
+}
+
+/// A copy into a staging buffer that is read back once mapping completes,
+/// after which the staging buffer is returned to the pool.
+pub(crate) struct Readback {
+    device: Device,
+    submission: Submission,
+    data: Signal<Result<Vec<u8>, BufferAsyncError>>,
+    rows: Option<RowPadding>,
+}
+
+/// The length of each row of data, and of each row in the staging buffer.
+#[derive(Clone, Copy)]
+struct RowPadding {
+    row: usize,
+    padded: usize,
+}
+
+impl StagingPool {
+    /// Takes a staging buffer of at least `size` bytes, creating one if none are free.
+    /// Sizes too large to pool get a buffer of exactly the aligned size.
+    pub fn take(&self, device: &Device, size: u64) -> Buffer {
+        let size = size.max(1).next_multiple_of(COPY_BUFFER_ALIGNMENT);
+        let bucket = size.next_power_of_two();
+        let pooled = bucket <= MAX_POOLED_SIZE.min(device.limits().max_buffer_size);
+        let size = if pooled { bucket } else { size };
+
+        let free = pooled
+            .then(|| self.buckets.lock().get_mut(&size).and_then(Vec::pop))
+            .flatten();
+        free.unwrap_or_else(|| {
+            device.create_buffer(&BufferDescriptor {
+                label: None,
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        })
+    }
+
+    /// Returns an unmapped staging buffer to the pool, dropping it if its
+    /// size isn't one of the pooled buckets.
+    pub fn recycle(&self, buffer: Buffer) {
+        let size = buffer.size();
+        if !size.is_power_of_two() || size > MAX_POOLED_SIZE {
+            return;
+        }
+
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(buffer.size()).or_default();
+        if bucket.len() < MAX_PER_BUCKET {
+            bucket.push(buffer);
+        }
+    }
+}
+
+impl Readback {
+    /// Records `copy` into a pooled staging buffer of at least `size` bytes,
+    /// submits it, and starts mapping the first `size` bytes.
+    pub fn new(gpu: &Gpu, size: u64, copy: impl FnOnce(&mut CommandEncoder, &Buffer)) -> Self {
+        let staging = gpu.staging_pool.take(&gpu.device, size);
+        let submission = gpu.immediate_dispatch(|encoder| copy(encoder, &staging));
+
+        let data = Signal::new();
+        let (signal, pool, mapped) = (data.clone(), gpu.staging_pool.clone(), staging.clone());
+        staging
+            .slice(..size)
+            .map_async(MapMode::Read, move |result| {
+                let result = result.map(|_| mapped.slice(..size).get_mapped_range().to_vec());
+                if result.is_ok() {
+                    mapped.unmap();
+                    pool.recycle(mapped);
+                }
+                signal.set(result);
+            });
+
+        Self {
+            device: gpu.device.clone(),
+            submission,
+            data,
+            rows: None,
+        }
+    }
+
+    /// Strips the padding from the end of each row once the data is read back,
+    /// for copies whose rows were padded to a multiple of `padded` bytes.
+    pub fn unpad_rows(mut self, row: usize, padded: usize) -> Self {
+        self.rows = (row != padded).then_some(RowPadding { row, padded });
+        self
+    }
+
+    /// Checks if the data has been read back, without blocking.
+    pub fn is_done(&self) -> bool {
+        self.submission.is_done() && self.data.is_set()
+    }
+
+    /// Blocks until the data has been read back.
+    pub fn wait(self) -> Vec<u8> {
+        self.submission.wait();
+        if !self.data.is_set() {
+            let _ = self.device.poll(MaintainBase::Wait);
+        }
+        // Another thread polling may have taken the callback but not run it yet.
+        self.data.wait();
+        unpad(self.data.take().unwrap().unwrap(), self.rows)
+    }
+
+    /// Waits for the data from any executor, polling the device in the background.
+    pub fn future(self) -> impl Future<Output = Vec<u8>> + Send + 'static {
+        // Called after the map was requested, so polling outlives its callback.
+        self.submission.poll_in_background();
+
+        let (data, rows) = (self.data.future(), self.rows);
+        async move { unpad(data.await.unwrap(), rows) }
+    }
+}
+
+fn unpad(data: Vec<u8>, rows: Option<RowPadding>) -> Vec<u8> {
+    match rows {
+        Some(RowPadding { row, padded }) => data
+            .chunks(padded)
+            .flat_map(|x| &x[..row])
+            .copied()
+            .collect(),
+        None => data,
+    }
+}
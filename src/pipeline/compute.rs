@@ -1,52 +1,187 @@
+use std::{path::Path, sync::Arc};
+
+use encase::{internal::WriteInto, ShaderType};
+use naga::ShaderStage;
 use nalgebra::Vector3;
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, BindGroupLayoutEntry, ComputePassDescriptor,
-    ComputePipelineDescriptor, PipelineCompilationOptions, PipelineLayoutDescriptor, ShaderModule,
-    ShaderModuleDescriptor, ShaderStages,
+    BindGroup, Buffer, CommandEncoder, ComputePassDescriptor, ComputePipelineDescriptor,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PushConstantRange, ShaderStages,
 };
 
 use crate::{
-    bindings::{Bindable, BindableResourceId},
+    bindings::{
+        buffer::{DispatchIndirectArgs, DynamicOffset, IndirectBuffer, UniformBuffer},
+        Bindable,
+    },
+    error::{Error, Result},
     gpu::Gpu,
     misc::ids::PipelineId,
+    shader::{PipelineSource, Preprocessor, Shader},
+    submission::Submission,
 };
 
-use super::PipelineStatus;
+use super::{
+    create_bind_group_layouts, create_bind_groups, dynamic_offsets,
+    hot_reload::{HotReload, ShaderFile},
+    push_constant_bytes, push_constant_range, resolve_bindings, BindingEntry, PipelineStatus,
+};
 
 pub struct ComputePipeline {
     gpu: Gpu,
 
     id: PipelineId,
     pipeline: wgpu::ComputePipeline,
-    entries: Vec<BindableResourceId>,
-    bind_group: BindGroup,
+    entries: Vec<BindingEntry>,
+    bind_groups: Vec<BindGroup>,
+    push_constants: Option<PushConstantRange>,
+    workgroup_size: Option<Vector3<u32>>,
+    bounds: Option<Arc<UniformBuffer<Vector3<u32>>>>,
+
+    hot_reload: Option<HotReload<ComputePipelineBuilder>>,
 }
 
+/// The number of workgroups to dispatch, either known on the CPU or read from a buffer.
+pub(crate) enum Workgroups<'a> {
+    Direct(Vector3<u32>),
+    Indirect(&'a Buffer, u64),
+}
+
+#[derive(Clone)]
 pub struct ComputePipelineBuilder {
     gpu: Gpu,
 
-    module: ShaderModule,
-    bind_group_layout: Vec<BindGroupLayoutEntry>,
-    entries: Vec<BindableResourceId>,
+    module: Result<Shader>,
+    file: Option<ShaderFile>,
+    entry_point: String,
+    constants: Vec<(String, f64)>,
+    entries: Vec<BindingEntry>,
+    group: u32,
+    push_constant_size: Option<u32>,
+    bounds: Option<Arc<UniformBuffer<Vector3<u32>>>>,
 }
 
 impl ComputePipeline {
     /// Dispatches the pipeline on the specified number of workgroups
-    pub fn dispatch(&mut self, workgroups: Vector3<u32>) {
-        self.dispatch_inner(workgroups, true);
+    pub fn dispatch(&mut self, workgroups: Vector3<u32>) -> Submission {
+        self.dispatch_inner(Workgroups::Direct(workgroups), &[], &[])
+    }
+
+    /// Dispatches the pipeline on the specified number of workgroups, pushing
+    /// `data` to the shader's `var<push_constant>` first.
+    pub fn dispatch_push<T: ShaderType + WriteInto>(
+        &mut self,
+        workgroups: Vector3<u32>,
+        data: &T,
+    ) -> Submission {
+        let bytes = self.push_bytes(data);
+        self.dispatch_inner(Workgroups::Direct(workgroups), &bytes, &[])
+    }
+
+    /// Dispatches the pipeline with the given elements of its dynamic uniform buffers
+    /// bound. Dynamic buffers without an offset bind their first element.
+    pub fn dispatch_dynamic(
+        &mut self,
+        workgroups: Vector3<u32>,
+        offsets: &[DynamicOffset],
+    ) -> Submission {
+        self.dispatch_inner(Workgroups::Direct(workgroups), &[], offsets)
     }
 
     pub fn dispatch_callback(
         &mut self,
         workgroups: Vector3<u32>,
         callback: impl FnOnce() + Send + 'static,
+    ) -> Submission {
+        let gpu = self.gpu.clone();
+        gpu.immediate_dispatch_callback(
+            |encoder| self.encode(encoder, Workgroups::Direct(workgroups), &[], &[]),
+            callback,
+        )
+    }
+
+    /// Dispatches enough workgroups to run the pipeline on at least the specified number
+    /// of invocations, using the workgroup size declared by the shader's entry point.
+    ///
+    /// If the builder was set up with [`ComputePipelineBuilder::invocation_bounds`], the
+    /// exact number of invocations is uploaded first so the shader can skip the extras.
+    /// The upload goes through the queue instead of being recorded with the dispatch, so
+    /// there are no queued or batched versions: any dispatch of this pipeline still in
+    /// the dispatch queue would see the new bounds too.
+    ///
+    /// Panics if the shader's workgroup size can't be read, for example if it isn't WGSL.
+    pub fn dispatch_threads(&mut self, threads: Vector3<u32>) -> Submission {
+        self.try_dispatch_threads(threads)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Dispatches like [`Self::dispatch_threads`], returning an error if the shader's
+    /// workgroup size can't be read instead of panicking.
+    pub fn try_dispatch_threads(&mut self, threads: Vector3<u32>) -> Result<Submission> {
+        let size = self.workgroup_size.ok_or_else(|| {
+            Error::Validation(
+                "Can't dispatch by invocation count, the shader's workgroup size is unknown".into(),
+            )
+        })?;
+
+        if let Some(bounds) = &self.bounds {
+            bounds.upload(&threads);
+        }
+
+        let workgroups = threads.zip_map(&size, |threads, size| threads.div_ceil(size));
+        Ok(self.dispatch_inner(Workgroups::Direct(workgroups), &[], &[]))
+    }
+
+    /// Dispatches the pipeline with the number of workgroups stored in the given
+    /// entry of the indirect buffer, which can be written by an earlier shader.
+    pub fn dispatch_indirect(
+        &mut self,
+        indirect: &IndirectBuffer<DispatchIndirectArgs>,
+        entry: u32,
+    ) -> Submission {
+        let offset = indirect.offset(entry..entry + 1);
+        let buffer = indirect.buffer();
+        let workgroups = Workgroups::Indirect(&buffer, offset);
+        self.dispatch_inner(workgroups, &[], &[])
+    }
+
+    /// Queues the pipeline like [`Self::dispatch_indirect`]. The arguments are
+    /// read when the queue runs, so an earlier queued dispatch can write them.
+    pub fn queue_dispatch_indirect(
+        &mut self,
+        indirect: &IndirectBuffer<DispatchIndirectArgs>,
+        entry: u32,
     ) {
-        self.dispatch_callback_inner(workgroups, callback, true);
+        let offset = indirect.offset(entry..entry + 1);
+        let buffer = indirect.buffer();
+        let workgroups = Workgroups::Indirect(&buffer, offset);
+        self.queue_dispatch_inner(workgroups, &[], &[]);
+    }
+
+    /// Returns the workgroup size declared by the shader's entry point, if it could be read.
+    pub fn workgroup_size(&self) -> Option<Vector3<u32>> {
+        self.workgroup_size
     }
 
     /// Queues the compute shader to run with the next compute dispach, render pass, or call to [`Gpu::flush_dispatch_queue`].
     pub fn queue_dispatch(&mut self, workgroups: Vector3<u32>) {
-        self.dispatch_inner(workgroups, false);
+        self.queue_dispatch_inner(Workgroups::Direct(workgroups), &[], &[]);
+    }
+
+    /// Queues the compute shader like [`Self::queue_dispatch`], pushing `data` to
+    /// the shader's `var<push_constant>` first. Each queued dispatch keeps its own value.
+    pub fn queue_dispatch_push<T: ShaderType + WriteInto>(
+        &mut self,
+        workgroups: Vector3<u32>,
+        data: &T,
+    ) {
+        let bytes = self.push_bytes(data);
+        self.queue_dispatch_inner(Workgroups::Direct(workgroups), &bytes, &[]);
+    }
+
+    /// Queues the compute shader like [`Self::dispatch_dynamic`]. Each queued
+    /// dispatch keeps its own offsets.
+    pub fn queue_dispatch_dynamic(&mut self, workgroups: Vector3<u32>, offsets: &[DynamicOffset]) {
+        self.queue_dispatch_inner(Workgroups::Direct(workgroups), &[], offsets);
     }
 
     pub fn queue_dispatch_callback(
@@ -54,127 +189,334 @@ impl ComputePipeline {
         workgroups: Vector3<u32>,
         callback: impl FnOnce() + Send + 'static,
     ) {
-        self.dispatch_callback_inner(workgroups, callback, false);
+        let gpu = self.gpu.clone();
+        gpu.queue_dispatch_callback(
+            |encoder| self.encode(encoder, Workgroups::Direct(workgroups), &[], &[]),
+            callback,
+        );
+    }
+
+    /// Returns the error from the last failed hot reload, if the pipeline was
+    /// created with [`Gpu::compute_pipeline_file`] and its shader currently fails to compile.
+    pub fn reload_error(&self) -> Option<&Error> {
+        self.hot_reload.as_ref().and_then(|x| x.error.as_ref())
     }
 
-    fn recreate_bind_group(&mut self) {
-        if self.gpu.binding_manager.get_pipeline(self.id).dirty {
-            self.bind_group = self.gpu.binding_manager.create_bind_group(
+    fn hot_reload(&mut self) {
+        let Some(hot_reload) = &mut self.hot_reload else {
+            return;
+        };
+
+        if !hot_reload.changed() {
+            return;
+        }
+
+        hot_reload.builder.module = hot_reload.load(&self.gpu);
+        match hot_reload.builder.create() {
+            Ok((pipeline, entries, bind_groups, push_constants)) => {
+                let status = PipelineStatus::new(&entries);
+                self.gpu.binding_manager.add_pipeline(self.id, status);
+                self.workgroup_size = hot_reload.builder.workgroup_size().unwrap_or_default();
+
+                self.pipeline = pipeline;
+                self.entries = entries;
+                self.bind_groups = bind_groups;
+                self.push_constants = push_constants;
+                hot_reload.error = None;
+            }
+            Err(err) => hot_reload.error = Some(err),
+        }
+    }
+
+    fn recreate_bind_groups(&mut self) {
+        for group in self.gpu.binding_manager.take_dirty_groups(self.id) {
+            self.bind_groups[group as usize] = self.gpu.binding_manager.bind_group(
                 &self.gpu.device,
-                &self.pipeline.get_bind_group_layout(0),
+                self.id,
+                &self.pipeline.get_bind_group_layout(group),
                 &self.entries,
-            )
+                group,
+            );
         }
     }
 
-    fn dispatch_inner(&mut self, workgroups: Vector3<u32>, immediate: bool) {
-        self.recreate_bind_group();
-        self.gpu.dispach(
-            |encoder| {
-                let mut compute_pass =
-                    encoder.begin_compute_pass(&ComputePassDescriptor::default());
-                compute_pass.set_pipeline(&self.pipeline);
-                compute_pass.set_bind_group(0, Some(&self.bind_group), &[]);
-                compute_pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
-            },
-            immediate,
-        );
+    /// Encodes a compute pass running the pipeline into the encoder.
+    pub(crate) fn encode(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        workgroups: Workgroups,
+        push: &[u8],
+        offsets: &[DynamicOffset],
+    ) {
+        self.hot_reload();
+        self.recreate_bind_groups();
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+        compute_pass.set_pipeline(&self.pipeline);
+        for (group, bind_group) in self.bind_groups.iter().enumerate() {
+            let offsets = dynamic_offsets(&self.entries, group as u32, offsets);
+            compute_pass.set_bind_group(group as u32, Some(bind_group), &offsets);
+        }
+        if !push.is_empty() {
+            compute_pass.set_push_constants(0, push);
+        }
+        match workgroups {
+            Workgroups::Direct(size) => compute_pass.dispatch_workgroups(size.x, size.y, size.z),
+            Workgroups::Indirect(buffer, offset) => {
+                compute_pass.dispatch_workgroups_indirect(buffer, offset)
+            }
+        }
+    }
+
+    /// Encodes a value to push as the pipeline's push constants.
+    pub(crate) fn push_bytes<T: ShaderType + WriteInto>(&self, data: &T) -> Vec<u8> {
+        push_constant_bytes(self.push_constants.as_ref(), data).1
     }
 
-    fn dispatch_callback_inner(
+    fn dispatch_inner(
         &mut self,
-        workgroups: Vector3<u32>,
-        callback: impl FnOnce() + Send + 'static,
-        immediate: bool,
+        workgroups: Workgroups,
+        push: &[u8],
+        offsets: &[DynamicOffset],
+    ) -> Submission {
+        let gpu = self.gpu.clone();
+        gpu.immediate_dispatch(|encoder| self.encode(encoder, workgroups, push, offsets))
+    }
+
+    fn queue_dispatch_inner(
+        &mut self,
+        workgroups: Workgroups,
+        push: &[u8],
+        offsets: &[DynamicOffset],
     ) {
-        self.recreate_bind_group();
-        self.gpu.dispach_callback(
-            |encoder| {
-                let mut compute_pass =
-                    encoder.begin_compute_pass(&ComputePassDescriptor::default());
-                compute_pass.set_pipeline(&self.pipeline);
-                compute_pass.set_bind_group(0, Some(&self.bind_group), &[]);
-                compute_pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
-            },
-            callback,
-            immediate,
-        );
+        let gpu = self.gpu.clone();
+        gpu.queue_dispatch(|encoder| self.encode(encoder, workgroups, push, offsets));
     }
 }
 
 impl ComputePipelineBuilder {
+    /// Sets the name of the compute entry point, which defaults to `main`.
+    pub fn entry_point(mut self, name: &str) -> Self {
+        self.entry_point = name.to_owned();
+        self
+    }
+
+    /// Sets the value of a WGSL `override` constant, by name or by its `@id`.
+    /// Booleans are set with `0` or `1`.
+    pub fn constant(mut self, key: &str, value: impl Into<f64>) -> Self {
+        self.constants.push((key.to_owned(), value.into()));
+        self
+    }
+
+    /// Reserves room for push constants of type `T`. This is only needed if the size
+    /// can't be read from the shader's `var<push_constant>`, for example if it isn't WGSL.
+    pub fn push_constants<T: ShaderType>(mut self) -> Self {
+        self.push_constant_size = Some(T::min_size().get() as u32);
+        self
+    }
+
+    /// Binds a `vec3<u32>` uniform to the WGSL variable with the given name, which
+    /// [`ComputePipeline::dispatch_threads`] sets to the number of invocations requested.
+    /// Invocations past it can then return early:
+    ///
+    /// ```wgsl
+    /// @group(0) @binding(1) var<uniform> bounds: vec3<u32>;
+    ///
+    /// @compute @workgroup_size(64)
+    /// fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    ///     if any(id >= bounds) { return; }
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// The variable is found by name, so [`Self::finish`] fails if the shader isn't WGSL.
+    pub fn invocation_bounds(mut self, name: &str) -> Self {
+        let bounds = self.gpu.create_uniform(&Vector3::<u32>::zeros());
+        self = self.bind_named(name, &bounds);
+        self.bounds = Some(Arc::new(bounds));
+        self
+    }
+
+    /// Starts a new bind group section. The following calls to [`Self::bind`] add
+    /// entries to `@group(group)`, starting at binding zero.
+    pub fn group(mut self, group: u32) -> Self {
+        self.group = group;
+        self
+    }
+
     /// Adds the supplied buffer as the next entry in the bind group, starting with binding zero and counting up.
+    /// Entries added with [`Self::bind_named`] don't count towards the binding index.
     pub fn bind(mut self, entry: &impl Bindable) -> Self {
-        self.entries.push(entry.resource_id());
-        self.bind_group_layout.push(BindGroupLayoutEntry {
-            binding: self.bind_group_layout.len() as u32,
-            visibility: ShaderStages::COMPUTE,
-            ty: entry.binding_type(),
-            count: entry.count(),
-        });
+        let binding = self
+            .entries
+            .iter()
+            .filter(|x| x.name.is_none() && x.group == self.group)
+            .count();
+        let entry = BindingEntry::new(entry, self.group, binding as u32, ShaderStages::COMPUTE);
+        self.entries.push(entry);
+        self
+    }
 
+    /// Binds the supplied buffer to the WGSL variable with the given name,
+    /// using the `@binding` it is declared with in the shader.
+    pub fn bind_named(mut self, name: &str, entry: &impl Bindable) -> Self {
+        self.entries.push(BindingEntry::named(name, entry));
         self
     }
 
     /// Converts the pipeline builder into an actual compte pipeline
     pub fn finish(self) -> ComputePipeline {
-        let device = &self.gpu.device;
-        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: None,
-            entries: &self.bind_group_layout,
-        });
-
-        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: None,
-            layout: Some(&layout),
-            module: &self.module,
-            entry_point: Some("main"),
-            // todo: pass in constants?
-            compilation_options: PipelineCompilationOptions::default(),
-            cache: None,
-        });
+        self.try_finish().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Converts the pipeline builder into an actual compute pipeline, returning
+    /// an error if the shader fails to compile or a bound resource doesn't match how the
+    /// shader declares it (address space, read-write access, texture kind, or buffer size).
+    pub fn try_finish(self) -> Result<ComputePipeline> {
+        let (pipeline, entries, bind_groups, push_constants) = self.create()?;
+        let workgroup_size = self.workgroup_size()?;
 
         let id = PipelineId::new();
-        self.gpu.binding_manager.add_pipeline(
-            id,
-            PipelineStatus {
-                resources: self.entries.clone(),
-                dirty: false,
-            },
-        );
+        self.gpu
+            .binding_manager
+            .add_pipeline(id, PipelineStatus::new(&entries));
 
-        ComputePipeline {
+        Ok(ComputePipeline {
             id,
-            bind_group: self.gpu.binding_manager.create_bind_group(
-                &self.gpu.device,
-                &pipeline.get_bind_group_layout(0),
-                &self.entries,
-            ),
-            gpu: self.gpu,
-            entries: self.entries,
+            bind_groups,
+            push_constants,
+            workgroup_size,
+            bounds: self.bounds.clone(),
+            gpu: self.gpu.clone(),
+            entries,
             pipeline,
-        }
+            hot_reload: self.file.clone().map(|file| HotReload::new(file, self)),
+        })
+    }
+
+    fn workgroup_size(&self) -> Result<Option<Vector3<u32>>> {
+        let Ok(shader) = &self.module else {
+            return Ok(None);
+        };
+
+        let size = shader.workgroup_size(&self.entry_point, &self.constants)?;
+        Ok(size.map(Vector3::from))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn create(
+        &self,
+    ) -> Result<(
+        wgpu::ComputePipeline,
+        Vec<BindingEntry>,
+        Vec<BindGroup>,
+        Option<PushConstantRange>,
+    )> {
+        let shader = self.module.clone()?;
+        shader.check_entry_point(&self.entry_point, ShaderStage::Compute)?;
+        let entry_points = [self.entry_point.as_str()];
+        shader.check_constants(&self.constants)?;
+        shader.workgroup_size(&self.entry_point, &self.constants)?;
+        let entries = resolve_bindings(&self.gpu, &shader, &entry_points, &self.entries)?;
+        let push_constants = push_constant_range(
+            &self.gpu,
+            &shader,
+            &entry_points,
+            self.push_constant_size,
+            ShaderStages::COMPUTE,
+        )?;
+        let module = shader.module;
+        let device = &self.gpu.device;
+
+        let constants = self
+            .constants
+            .iter()
+            .map(|(key, value)| (key.as_str(), *value))
+            .collect::<Vec<_>>();
+        let compilation_options = PipelineCompilationOptions {
+            constants: &constants,
+            ..PipelineCompilationOptions::default()
+        };
+
+        self.gpu.error_scope(|| {
+            let bind_group_layouts = create_bind_group_layouts(&self.gpu, &entries);
+
+            let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+                push_constant_ranges: push_constants.as_slice(),
+            });
+
+            let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(&self.entry_point),
+                compilation_options,
+                cache: None,
+            });
+
+            let bind_groups = create_bind_groups(
+                &self.gpu,
+                |group| pipeline.get_bind_group_layout(group),
+                &entries,
+            );
+
+            (pipeline, entries, bind_groups, push_constants)
+        })
     }
 }
 
 impl Gpu {
     /// Creates a new compute pipeline builder with the specified shader module.
-    /// The compute entrypoint must be a function named `main`.
-    pub fn compute_pipeline(&self, source: ShaderModuleDescriptor) -> ComputePipelineBuilder {
-        let module = self.device.create_shader_module(source);
+    /// The compute entrypoint defaults to a function named `main`, but can be changed with
+    /// [`ComputePipelineBuilder::entry_point`]. To create several pipelines from one
+    /// module without compiling it again, pass in a [`Shader`] from [`Gpu::create_shader`].
+    ///
+    /// Shader compilation errors are reported when the pipeline is finished.
+    pub fn compute_pipeline<'a>(
+        &self,
+        source: impl Into<PipelineSource<'a>>,
+    ) -> ComputePipelineBuilder {
+        self.compute_pipeline_inner(self.try_create_shader(source), None)
+    }
+
+    /// Creates a new compute pipeline builder from a WGSL file that is loaded at runtime.
+    ///
+    /// The pipeline will watch the file and rebuild itself with the same bindings whenever it changes.
+    /// If the new source fails to compile, the previous pipeline is kept and the error can be
+    /// read with [`ComputePipeline::reload_error`].
+    pub fn compute_pipeline_file(&self, path: impl AsRef<Path>) -> ComputePipelineBuilder {
+        self.compute_pipeline_file_with(path, Preprocessor::new())
+    }
+
+    /// Like [`Gpu::compute_pipeline_file`], but runs the file through the given
+    /// preprocessor, for its search paths and defines. Included files are watched too.
+    pub fn compute_pipeline_file_with(
+        &self,
+        path: impl AsRef<Path>,
+        preprocessor: Preprocessor,
+    ) -> ComputePipelineBuilder {
+        let mut file = ShaderFile::new(path.as_ref(), preprocessor);
+        self.compute_pipeline_inner(file.load(self), Some(file))
+    }
 
+    fn compute_pipeline_inner(
+        &self,
+        module: Result<Shader>,
+        file: Option<ShaderFile>,
+    ) -> ComputePipelineBuilder {
         ComputePipelineBuilder {
             gpu: self.clone(),
 
             module,
-            bind_group_layout: Vec::new(),
+            file,
+            entry_point: "main".into(),
+            constants: Vec::new(),
             entries: Vec::new(),
+            group: 0,
+            push_constant_size: None,
+            bounds: None,
         }
     }
 }
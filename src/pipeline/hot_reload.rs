@@ -0,0 +1,96 @@
+//! Rebuilds pipelines when their shader file changes on disk.
+//!
+//! Pipelines loaded from a file check the modification time of it and every file
+//! it includes before each dispatch or draw, and rebuild themselves from a copy of
+//! their builder when any of them change.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    error::{Error, Result},
+    gpu::Gpu,
+    shader::{Preprocessor, Shader},
+};
+
+/// Minimum time between checking the shader files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub(crate) struct HotReload<Builder> {
+    pub builder: Builder,
+    pub error: Option<Error>,
+
+    file: ShaderFile,
+    modified: Vec<Option<SystemTime>>,
+    last_check: Instant,
+}
+
+/// A shader file along with the preprocessor it is run through and the files it included.
+#[derive(Clone)]
+pub(crate) struct ShaderFile {
+    path: PathBuf,
+    preprocessor: Preprocessor,
+    dependencies: Vec<PathBuf>,
+}
+
+impl<Builder> HotReload<Builder> {
+    pub fn new(file: ShaderFile, builder: Builder) -> Self {
+        Self {
+            builder,
+            error: None,
+
+            modified: file.modified(),
+            file,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns true if any of the files have been modified since the last time this returned true.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+
+        self.last_check = Instant::now();
+        let modified = self.file.modified();
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    /// Loads the shader again, watching whichever files it includes now.
+    pub fn load(&mut self, gpu: &Gpu) -> Result<Shader> {
+        let shader = self.file.load(gpu);
+        self.modified = self.file.modified();
+        shader
+    }
+}
+
+impl ShaderFile {
+    pub fn new(path: &Path, preprocessor: Preprocessor) -> Self {
+        Self {
+            path: path.to_owned(),
+            preprocessor,
+            dependencies: vec![path.to_owned()],
+        }
+    }
+
+    /// Preprocesses the file and creates a shader module from it. If it can't
+    /// be preprocessed, the files from the last successful load are still watched.
+    pub fn load(&mut self, gpu: &Gpu) -> Result<Shader> {
+        let shader = self.preprocessor.process_file(&self.path)?;
+        self.dependencies = shader.dependencies().to_vec();
+        gpu.try_create_shader(&shader)
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.dependencies.iter().map(|x| modified(x)).collect()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|x| x.modified()).ok()
+}
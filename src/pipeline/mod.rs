@@ -1,11 +1,233 @@
 //! Compute and render pipelines.
 
-use crate::bindings::BindableResourceId;
+use encase::{internal::WriteInto, ShaderType, StorageBuffer};
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    Features, PushConstantRange, ShaderStages,
+};
+
+use crate::{
+    bindings::{buffer::DynamicOffset, Bindable, BindableResourceId},
+    error::{Error, Result},
+    gpu::Gpu,
+    shader::Shader,
+};
 
 pub mod compute;
+mod hot_reload;
 pub mod render;
 
 pub(crate) struct PipelineStatus {
-    pub resources: Vec<BindableResourceId>,
-    pub dirty: bool,
+    /// The resources bound in each bind group.
+    pub resources: Vec<Vec<BindableResourceId>>,
+    /// Which bind groups need to be recreated.
+    pub dirty: Vec<bool>,
+    /// Recently created bind groups, keyed by the generations of their resources.
+    pub cache: Vec<Vec<(Vec<u64>, BindGroup)>>,
+}
+
+impl PipelineStatus {
+    pub fn new(entries: &[BindingEntry]) -> Self {
+        let groups = group_count(entries);
+        Self {
+            resources: (0..groups)
+                .map(|group| {
+                    entries
+                        .iter()
+                        .filter(|x| x.group == group)
+                        .map(|x| x.resource)
+                        .collect()
+                })
+                .collect(),
+            dirty: vec![false; groups as usize],
+            cache: vec![Vec::new(); groups as usize],
+        }
+    }
+}
+
+/// A resource bound to a pipeline. Entries bound by name get their binding
+/// and visibility from the shader when the pipeline is created.
+#[derive(Clone)]
+pub(crate) struct BindingEntry {
+    pub name: Option<String>,
+    pub group: u32,
+    pub layout: BindGroupLayoutEntry,
+    pub resource: BindableResourceId,
+}
+
+impl BindingEntry {
+    pub fn new(entry: &impl Bindable, group: u32, binding: u32, visibility: ShaderStages) -> Self {
+        Self {
+            name: None,
+            group,
+            layout: BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: entry.binding_type(),
+                count: entry.count(),
+            },
+            resource: entry.resource_id(),
+        }
+    }
+
+    pub fn named(name: &str, entry: &impl Bindable) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            ..Self::new(entry, 0, 0, ShaderStages::NONE)
+        }
+    }
+}
+
+/// Looks up the bindings of named entries in the shader, then checks that
+/// every binding matches how the shader declares it.
+pub(crate) fn resolve_bindings(
+    gpu: &Gpu,
+    shader: &Shader,
+    entry_points: &[&str],
+    entries: &[BindingEntry],
+) -> Result<Vec<BindingEntry>> {
+    let Some(reflection) = &shader.reflection else {
+        return match entries.iter().find_map(|x| x.name.as_ref()) {
+            Some(name) => Err(Error::Validation(format!(
+                "Can't bind `{name}` by name, only WGSL shaders support named bindings"
+            ))),
+            None => Ok(entries.to_vec()),
+        };
+    };
+
+    let mut out = entries.to_vec();
+    for entry in out.iter_mut() {
+        let Some(name) = &entry.name else { continue };
+        let (binding, visibility) = reflection.find(name, entry_points).ok_or_else(|| {
+            Error::Validation(format!(
+                "The shader has no resource variable named `{name}`"
+            ))
+        })?;
+        entry.group = binding.group;
+        entry.layout.binding = binding.binding;
+        entry.layout.visibility = visibility;
+    }
+
+    reflection.check_bindings(&gpu.binding_manager, entry_points, &out)?;
+    Ok(out)
+}
+
+/// Returns the number of bind groups needed for the entries. There is always
+/// at least one, and groups without any entries are left empty.
+pub(crate) fn group_count(entries: &[BindingEntry]) -> u32 {
+    entries.iter().map(|x| x.group + 1).max().unwrap_or(1)
+}
+
+/// Picks the offset of each dynamic buffer in the group, ordered by binding as
+/// wgpu expects. Buffers without a matching offset bind their first element.
+pub(crate) fn dynamic_offsets(
+    entries: &[BindingEntry],
+    group: u32,
+    offsets: &[DynamicOffset],
+) -> Vec<u32> {
+    let mut dynamic = entries
+        .iter()
+        .filter(|x| x.group == group)
+        .filter(|x| {
+            matches!(
+                x.layout.ty,
+                BindingType::Buffer {
+                    has_dynamic_offset: true,
+                    ..
+                }
+            )
+        })
+        .collect::<Vec<_>>();
+    dynamic.sort_by_key(|x| x.layout.binding);
+
+    dynamic
+        .iter()
+        .map(|entry| {
+            offsets
+                .iter()
+                .find(|x| entry.resource == BindableResourceId::Buffer(x.buffer))
+                .map_or(0, |x| x.offset)
+        })
+        .collect()
+}
+
+/// Creates the layout of every bind group, in order.
+pub(crate) fn create_bind_group_layouts(
+    gpu: &Gpu,
+    entries: &[BindingEntry],
+) -> Vec<BindGroupLayout> {
+    (0..group_count(entries))
+        .map(|group| {
+            let entries = entries
+                .iter()
+                .filter(|x| x.group == group)
+                .map(|x| x.layout)
+                .collect::<Vec<_>>();
+            gpu.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &entries,
+                })
+        })
+        .collect()
+}
+
+/// Creates every bind group, in order.
+pub(crate) fn create_bind_groups(
+    gpu: &Gpu,
+    layout: impl Fn(u32) -> BindGroupLayout,
+    entries: &[BindingEntry],
+) -> Vec<BindGroup> {
+    (0..group_count(entries))
+        .map(|group| {
+            gpu.binding_manager
+                .create_bind_group(&gpu.device, &layout(group), entries, group)
+        })
+        .collect()
+}
+
+/// Returns the push constant range of a pipeline. It is sized to fit both the size
+/// requested with the builder and the `var<push_constant>` declared in the shader.
+pub(crate) fn push_constant_range(
+    gpu: &Gpu,
+    shader: &Shader,
+    entry_points: &[&str],
+    size: Option<u32>,
+    stages: ShaderStages,
+) -> Result<Option<PushConstantRange>> {
+    let reflected = shader
+        .reflection
+        .as_ref()
+        .and_then(|x| x.push_constants(entry_points));
+    let (size, stages) = match (size, reflected) {
+        (None, None) => return Ok(None),
+        (Some(size), None) => (size, stages),
+        (None, Some(reflected)) => reflected,
+        (Some(size), Some((reflected, stages))) => (size.max(reflected), stages),
+    };
+
+    if !gpu.device.features().contains(Features::PUSH_CONSTANTS) {
+        return Err(Error::Validation(
+            "The pipeline uses push constants, but they weren't enabled with `GpuBuilder::with_push_constants`".into(),
+        ));
+    }
+
+    Ok(Some(PushConstantRange {
+        stages,
+        range: 0..size,
+    }))
+}
+
+/// Encodes a value to push as push constants.
+pub(crate) fn push_constant_bytes<T: ShaderType + WriteInto>(
+    range: Option<&PushConstantRange>,
+    data: &T,
+) -> (ShaderStages, Vec<u8>) {
+    let range = range.unwrap_or_else(|| {
+        panic!("The pipeline has no push constants, declare a `var<push_constant>` in the shader or size them with the builder's `push_constants`")
+    });
+
+    let mut bytes = Vec::new();
+    StorageBuffer::new(&mut bytes).write(data).unwrap();
+    (range.stages, bytes)
 }
@@ -1,27 +1,36 @@
-use std::ops::Range;
+use std::{mem, ops::Range, path::Path};
 
 use consts::VERTEX_BUFFER_LAYOUT;
-use encase::ShaderType;
+use encase::{internal::WriteInto, ShaderType};
+use naga::ShaderStage;
 use nalgebra::{Vector2, Vector4};
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BlendComponent, BlendState,
-    ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
-    FragmentState, IndexFormat, MultisampleState, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, RenderPass, ShaderModule,
-    ShaderModuleDescriptor, ShaderStages, StencilState, VertexBufferLayout, VertexState,
+    BindGroup, BlendComponent, BlendState, Buffer, ColorTargetState, ColorWrites, CompareFunction,
+    DepthBiasState, DepthStencilState, Features, FragmentState, IndexFormat, MultisampleState,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
+    PushConstantRange, RenderPass, ShaderStages, StencilState, VertexBufferLayout, VertexState,
 };
 
 use crate::{
     bindings::{
-        buffer::{IndexBuffer, VertexBuffer},
-        Bindable, BindableResourceId,
+        buffer::{
+            DrawIndexedIndirectArgs, DrawIndirectArgs, DynamicOffset, IndexBuffer, IndirectBuffer,
+            VertexBuffer,
+        },
+        Bindable,
     },
+    error::{Error, Result},
     gpu::Gpu,
     misc::ids::PipelineId,
+    shader::{PipelineSource, Preprocessor, Shader},
     DEPTH_TEXTURE_FORMAT, TEXTURE_FORMAT,
 };
 
-use super::PipelineStatus;
+use super::{
+    create_bind_group_layouts, create_bind_groups, dynamic_offsets,
+    hot_reload::{HotReload, ShaderFile},
+    push_constant_bytes, push_constant_range, resolve_bindings, BindingEntry, PipelineStatus,
+};
 pub mod consts;
 pub mod pass;
 
@@ -42,31 +51,72 @@ pub struct RenderPipeline {
 
     id: PipelineId,
     pipeline: wgpu::RenderPipeline,
-    entries: Vec<BindableResourceId>,
-    bind_group: BindGroup,
+    entries: Vec<BindingEntry>,
+    bind_groups: Vec<BindGroup>,
+    push_constants: Option<PushConstantRange>,
+
+    hot_reload: Option<HotReload<RenderPipelineBuilder>>,
 }
 
 #[derive(Clone)]
 pub struct RenderPipelineBuilder {
     gpu: Gpu,
 
-    module: ShaderModule,
+    module: Result<Shader>,
+    file: Option<ShaderFile>,
     vertex_layout: VertexBufferLayout<'static>,
     instance_layout: Option<VertexBufferLayout<'static>>,
-    bind_group_layout: Vec<BindGroupLayoutEntry>,
-    bind_group: Vec<BindableResourceId>,
+    vertex_entry: String,
+    fragment_entry: String,
+    constants: Vec<(String, f64)>,
+    entries: Vec<BindingEntry>,
+    group: u32,
+    push_constant_size: Option<u32>,
 
     topology: PrimitiveTopology,
     depth_compare: CompareFunction,
 }
 
 impl RenderPipeline {
-    fn recreate_bind_group(&mut self) {
-        if self.gpu.binding_manager.get_pipeline(self.id).dirty {
-            self.bind_group = self.gpu.binding_manager.create_bind_group(
+    /// Returns the error from the last failed hot reload, if the pipeline was
+    /// created with [`Gpu::render_pipeline_file`] and its shader currently fails to compile.
+    pub fn reload_error(&self) -> Option<&Error> {
+        self.hot_reload.as_ref().and_then(|x| x.error.as_ref())
+    }
+
+    fn hot_reload(&mut self) {
+        let Some(hot_reload) = &mut self.hot_reload else {
+            return;
+        };
+
+        if !hot_reload.changed() {
+            return;
+        }
+
+        hot_reload.builder.module = hot_reload.load(&self.gpu);
+        match hot_reload.builder.create() {
+            Ok((pipeline, entries, bind_groups, push_constants)) => {
+                let status = PipelineStatus::new(&entries);
+                self.gpu.binding_manager.add_pipeline(self.id, status);
+
+                self.pipeline = pipeline;
+                self.entries = entries;
+                self.bind_groups = bind_groups;
+                self.push_constants = push_constants;
+                hot_reload.error = None;
+            }
+            Err(err) => hot_reload.error = Some(err),
+        }
+    }
+
+    fn recreate_bind_groups(&mut self) {
+        for group in self.gpu.binding_manager.take_dirty_groups(self.id) {
+            self.bind_groups[group as usize] = self.gpu.binding_manager.bind_group(
                 &self.gpu.device,
-                &self.pipeline.get_bind_group_layout(0),
+                self.id,
+                &self.pipeline.get_bind_group_layout(group),
                 &self.entries,
+                group,
             );
         }
     }
@@ -78,56 +128,255 @@ impl RenderPipeline {
         vertex: &VertexBuffer<T>,
         indices: Range<u32>,
     ) {
-        self.recreate_bind_group();
+        self.draw_inner(render_pass, index, vertex, indices, None, &[]);
+    }
 
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+    /// Draws like [`Self::draw`], pushing `data` to the shader's `var<push_constant>` first.
+    pub fn draw_push<T, P: ShaderType + WriteInto>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        index: &IndexBuffer,
+        vertex: &VertexBuffer<T>,
+        indices: Range<u32>,
+        data: &P,
+    ) {
+        let push = push_constant_bytes(self.push_constants.as_ref(), data);
+        self.draw_inner(render_pass, index, vertex, indices, Some(push), &[]);
+    }
+
+    /// Draws like [`Self::draw`] with the given elements of the pipeline's dynamic
+    /// uniform buffers bound. Dynamic buffers without an offset bind their first element.
+    pub fn draw_dynamic<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        index: &IndexBuffer,
+        vertex: &VertexBuffer<T>,
+        indices: Range<u32>,
+        offsets: &[DynamicOffset],
+    ) {
+        self.draw_inner(render_pass, index, vertex, indices, None, offsets);
+    }
+
+    /// Draws with the arguments stored in the given entry of the indirect buffer,
+    /// which can be written by a compute shader.
+    pub fn draw_indirect<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        vertex: &VertexBuffer<T>,
+        indirect: &IndirectBuffer<DrawIndirectArgs>,
+        entry: u32,
+    ) {
+        self.multi_draw_indirect(render_pass, vertex, indirect, entry..entry + 1);
+    }
+
+    /// Draws with the arguments stored in the given entry of the indirect buffer,
+    /// which can be written by a compute shader.
+    pub fn draw_indexed_indirect<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        index: &IndexBuffer,
+        vertex: &VertexBuffer<T>,
+        indirect: &IndirectBuffer<DrawIndexedIndirectArgs>,
+        entry: u32,
+    ) {
+        self.multi_draw_indexed_indirect(render_pass, index, vertex, indirect, entry..entry + 1);
+    }
+
+    /// Issues one draw for each entry of the indirect buffer in the range.
+    /// Without [`crate::gpu::GpuBuilder::with_multi_draw_indirect`] the entries are drawn one by one.
+    pub fn multi_draw_indirect<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        vertex: &VertexBuffer<T>,
+        indirect: &IndirectBuffer<DrawIndirectArgs>,
+        entries: Range<u32>,
+    ) {
+        let offset = indirect.offset(entries.clone());
+        self.set_pipeline(render_pass, None, &[]);
+        render_pass.set_vertex_buffer(0, vertex.get().slice(..));
+
+        let indirect = indirect.buffer();
+        if self.multi_draw(&entries) {
+            render_pass.multi_draw_indirect(&indirect, offset, entries.len() as u32);
+        } else {
+            for entry in 0..entries.len() as u64 {
+                render_pass.draw_indirect(
+                    &indirect,
+                    offset + entry * mem::size_of::<DrawIndirectArgs>() as u64,
+                );
+            }
+        }
+    }
+
+    /// Issues one indexed draw for each entry of the indirect buffer in the range.
+    /// Without [`crate::gpu::GpuBuilder::with_multi_draw_indirect`] the entries are drawn one by one.
+    pub fn multi_draw_indexed_indirect<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        index: &IndexBuffer,
+        vertex: &VertexBuffer<T>,
+        indirect: &IndirectBuffer<DrawIndexedIndirectArgs>,
+        entries: Range<u32>,
+    ) {
+        let offset = indirect.offset(entries.clone());
+        self.set_pipeline(render_pass, None, &[]);
         render_pass.set_index_buffer(index.get().slice(..), IndexFormat::Uint32);
         render_pass.set_vertex_buffer(0, vertex.get().slice(..));
-        render_pass.draw_indexed(indices, 0, 0..1);
+
+        let indirect = indirect.buffer();
+        if self.multi_draw(&entries) {
+            render_pass.multi_draw_indexed_indirect(&indirect, offset, entries.len() as u32);
+        } else {
+            for entry in 0..entries.len() as u64 {
+                let offset = offset + entry * mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+                render_pass.draw_indexed_indirect(&indirect, offset);
+            }
+        }
+    }
+
+    fn multi_draw(&self, entries: &Range<u32>) -> bool {
+        entries.len() > 1 && self.gpu.features().contains(Features::MULTI_DRAW_INDIRECT)
     }
 
     pub fn draw_quad(&mut self, render_pass: &mut RenderPass, instances: Range<u32>) {
-        self.recreate_bind_group();
-        let (vertex, index) = self.gpu.default_buffers();
+        self.instance_quad_inner(render_pass, None, instances, None, &[]);
+    }
+
+    /// Draws like [`Self::draw_quad`], pushing `data` to the shader's `var<push_constant>` first.
+    pub fn draw_quad_push<P: ShaderType + WriteInto>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        instances: Range<u32>,
+        data: &P,
+    ) {
+        let push = push_constant_bytes(self.push_constants.as_ref(), data);
+        self.instance_quad_inner(render_pass, None, instances, Some(push), &[]);
+    }
+
+    /// Draws like [`Self::draw_quad`] with the given elements of the pipeline's
+    /// dynamic uniform buffers bound.
+    pub fn draw_quad_dynamic(
+        &mut self,
+        render_pass: &mut RenderPass,
+        instances: Range<u32>,
+        offsets: &[DynamicOffset],
+    ) {
+        self.instance_quad_inner(render_pass, None, instances, None, offsets);
+    }
+
+    pub fn instance_quad<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        instances: &VertexBuffer<T>,
+        range: Range<u32>,
+    ) {
+        self.instance_quad_inner(render_pass, Some(&instances.get()), range, None, &[]);
+    }
+
+    /// Draws like [`Self::instance_quad`], pushing `data` to the shader's `var<push_constant>` first.
+    pub fn instance_quad_push<T, P: ShaderType + WriteInto>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        instances: &VertexBuffer<T>,
+        range: Range<u32>,
+        data: &P,
+    ) {
+        let push = push_constant_bytes(self.push_constants.as_ref(), data);
+        let instances = instances.get();
+        self.instance_quad_inner(render_pass, Some(&instances), range, Some(push), &[]);
+    }
+
+    fn set_pipeline(
+        &mut self,
+        render_pass: &mut RenderPass,
+        push: Option<(ShaderStages, Vec<u8>)>,
+        offsets: &[DynamicOffset],
+    ) {
+        self.hot_reload();
+        self.recreate_bind_groups();
 
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+        for (group, bind_group) in self.bind_groups.iter().enumerate() {
+            let offsets = dynamic_offsets(&self.entries, group as u32, offsets);
+            render_pass.set_bind_group(group as u32, Some(bind_group), &offsets);
+        }
+        if let Some((stages, data)) = push {
+            render_pass.set_push_constants(stages, 0, &data);
+        }
+    }
+
+    fn draw_inner<T>(
+        &mut self,
+        render_pass: &mut RenderPass,
+        index: &IndexBuffer,
+        vertex: &VertexBuffer<T>,
+        indices: Range<u32>,
+        push: Option<(ShaderStages, Vec<u8>)>,
+        offsets: &[DynamicOffset],
+    ) {
+        self.set_pipeline(render_pass, push, offsets);
         render_pass.set_index_buffer(index.get().slice(..), IndexFormat::Uint32);
         render_pass.set_vertex_buffer(0, vertex.get().slice(..));
-        render_pass.draw_indexed(0..6, 0, instances);
+        render_pass.draw_indexed(indices, 0, 0..1);
     }
 
-    pub fn instance_quad<T>(
+    fn instance_quad_inner(
         &mut self,
         render_pass: &mut RenderPass,
-        instances: &VertexBuffer<T>,
+        instances: Option<&Buffer>,
         range: Range<u32>,
+        push: Option<(ShaderStages, Vec<u8>)>,
+        offsets: &[DynamicOffset],
     ) {
-        self.recreate_bind_group();
+        self.set_pipeline(render_pass, push, offsets);
         let (vertex, index) = self.gpu.default_buffers();
 
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
         render_pass.set_index_buffer(index.get().slice(..), IndexFormat::Uint32);
         render_pass.set_vertex_buffer(0, vertex.get().slice(..));
-        render_pass.set_vertex_buffer(1, instances.get().slice(..));
+        if let Some(instances) = instances {
+            render_pass.set_vertex_buffer(1, instances.slice(..));
+        }
         render_pass.draw_indexed(0..6, 0, range);
     }
 }
 
 impl RenderPipelineBuilder {
+    /// Sets the value of a WGSL `override` constant, by name or by its `@id`.
+    /// Booleans are set with `0` or `1`.
+    pub fn constant(mut self, key: &str, value: impl Into<f64>) -> Self {
+        self.constants.push((key.to_owned(), value.into()));
+        self
+    }
+
+    /// Starts a new bind group section. The following calls to [`Self::bind`] add
+    /// entries to `@group(group)`, starting at binding zero.
+    pub fn group(mut self, group: u32) -> Self {
+        self.group = group;
+        self
+    }
+
     pub fn bind(mut self, entry: &impl Bindable, visibility: ShaderStages) -> Self {
-        let binding = self.bind_group.len() as u32;
+        let binding = self
+            .entries
+            .iter()
+            .filter(|x| x.name.is_none() && x.group == self.group)
+            .count();
+        let entry = BindingEntry::new(entry, self.group, binding as u32, visibility);
+        self.entries.push(entry);
+        self
+    }
 
-        self.bind_group.push(entry.resource_id());
-        self.bind_group_layout.push(BindGroupLayoutEntry {
-            binding,
-            visibility,
-            ty: entry.binding_type(),
-            count: entry.count(),
-        });
+    /// Binds the supplied resource to the WGSL variable with the given name, using the
+    /// `@binding` it is declared with and making it visible to the stages that use it.
+    pub fn bind_named(mut self, name: &str, entry: &impl Bindable) -> Self {
+        self.entries.push(BindingEntry::named(name, entry));
+        self
+    }
 
+    /// Reserves room for push constants of type `T`, visible to both stages. This is only
+    /// needed if the size can't be read from the shader's `var<push_constant>`.
+    pub fn push_constants<T: ShaderType>(mut self) -> Self {
+        self.push_constant_size = Some(T::min_size().get() as u32);
         self
     }
 
@@ -141,6 +390,18 @@ impl RenderPipelineBuilder {
         self
     }
 
+    /// Sets the name of the vertex entry point, which defaults to `vert`.
+    pub fn vertex_entry(mut self, name: &str) -> Self {
+        self.vertex_entry = name.to_owned();
+        self
+    }
+
+    /// Sets the name of the fragment entry point, which defaults to `frag`.
+    pub fn fragment_entry(mut self, name: &str) -> Self {
+        self.fragment_entry = name.to_owned();
+        self
+    }
+
     pub fn depth_compare(mut self, compare: CompareFunction) -> Self {
         self.depth_compare = compare;
         self
@@ -152,98 +413,173 @@ impl RenderPipelineBuilder {
     }
 
     pub fn finish(self) -> RenderPipeline {
-        let device = &self.gpu.device;
-
-        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: None,
-            entries: &self.bind_group_layout,
-        });
-
-        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let mut vertex_buffers = vec![self.vertex_layout];
-        if let Some(layout) = self.instance_layout {
-            vertex_buffers.push(layout);
-        }
+        self.try_finish().unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&layout),
-            vertex: VertexState {
-                module: &self.module,
-                entry_point: Some("vert"),
-                buffers: &vertex_buffers,
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &self.module,
-                entry_point: Some("frag"),
-                targets: &[Some(ColorTargetState {
-                    format: TEXTURE_FORMAT,
-                    blend: Some(BlendState {
-                        color: BlendComponent::OVER,
-                        alpha: BlendComponent::OVER,
-                    }),
-                    write_mask: ColorWrites::all(),
-                })],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: self.topology,
-                ..PrimitiveState::default()
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: DEPTH_TEXTURE_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: self.depth_compare,
-                stencil: StencilState::default(),
-                bias: DepthBiasState::default(),
-            }),
-            multisample: MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
-
-        let bind_group = self.gpu.binding_manager.create_bind_group(
-            &self.gpu.device,
-            &pipeline.get_bind_group_layout(0),
-            &self.bind_group,
-        );
+    /// Converts the pipeline builder into an actual render pipeline, returning
+    /// an error if the shader fails to compile or a bound resource doesn't match how the
+    /// shader declares it (address space, read-write access, texture kind, or buffer size).
+    pub fn try_finish(self) -> Result<RenderPipeline> {
+        let (pipeline, entries, bind_groups, push_constants) = self.create()?;
 
         let id = PipelineId::new();
-        self.gpu.binding_manager.add_pipeline(
-            id,
-            PipelineStatus {
-                resources: self.bind_group.clone(),
-                dirty: false,
-            },
-        );
-
-        RenderPipeline {
-            gpu: self.gpu,
+        self.gpu
+            .binding_manager
+            .add_pipeline(id, PipelineStatus::new(&entries));
+
+        Ok(RenderPipeline {
+            gpu: self.gpu.clone(),
             id,
             pipeline,
-            bind_group,
-            entries: self.bind_group,
-        }
+            bind_groups,
+            push_constants,
+            entries,
+            hot_reload: self.file.clone().map(|file| HotReload::new(file, self)),
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn create(
+        &self,
+    ) -> Result<(
+        wgpu::RenderPipeline,
+        Vec<BindingEntry>,
+        Vec<BindGroup>,
+        Option<PushConstantRange>,
+    )> {
+        let shader = self.module.clone()?;
+        shader.check_entry_point(&self.vertex_entry, ShaderStage::Vertex)?;
+        shader.check_entry_point(&self.fragment_entry, ShaderStage::Fragment)?;
+        let entry_points = [self.vertex_entry.as_str(), self.fragment_entry.as_str()];
+        shader.check_constants(&self.constants)?;
+        let entries = resolve_bindings(&self.gpu, &shader, &entry_points, &self.entries)?;
+        let push_constants = push_constant_range(
+            &self.gpu,
+            &shader,
+            &entry_points,
+            self.push_constant_size,
+            ShaderStages::VERTEX_FRAGMENT,
+        )?;
+        let module = shader.module;
+        let device = &self.gpu.device;
+
+        let constants = self
+            .constants
+            .iter()
+            .map(|(key, value)| (key.as_str(), *value))
+            .collect::<Vec<_>>();
+        let compilation_options = PipelineCompilationOptions {
+            constants: &constants,
+            ..PipelineCompilationOptions::default()
+        };
+
+        self.gpu.error_scope(|| {
+            let bind_group_layouts = create_bind_group_layouts(&self.gpu, &entries);
+
+            let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+                push_constant_ranges: push_constants.as_slice(),
+            });
+
+            let mut vertex_buffers = vec![self.vertex_layout.clone()];
+            if let Some(layout) = &self.instance_layout {
+                vertex_buffers.push(layout.clone());
+            }
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: Some(&self.vertex_entry),
+                    buffers: &vertex_buffers,
+                    compilation_options: compilation_options.clone(),
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: Some(&self.fragment_entry),
+                    targets: &[Some(ColorTargetState {
+                        format: TEXTURE_FORMAT,
+                        blend: Some(BlendState {
+                            color: BlendComponent::OVER,
+                            alpha: BlendComponent::OVER,
+                        }),
+                        write_mask: ColorWrites::all(),
+                    })],
+                    compilation_options,
+                }),
+                primitive: PrimitiveState {
+                    topology: self.topology,
+                    ..PrimitiveState::default()
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: DEPTH_TEXTURE_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: self.depth_compare,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+            let bind_groups = create_bind_groups(
+                &self.gpu,
+                |group| pipeline.get_bind_group_layout(group),
+                &entries,
+            );
+
+            (pipeline, entries, bind_groups, push_constants)
+        })
     }
 }
 
 impl Gpu {
-    pub fn render_pipeline(&self, source: ShaderModuleDescriptor) -> RenderPipelineBuilder {
-        let module = self.device.create_shader_module(source);
+    pub fn render_pipeline<'a>(
+        &self,
+        source: impl Into<PipelineSource<'a>>,
+    ) -> RenderPipelineBuilder {
+        self.render_pipeline_inner(self.try_create_shader(source), None)
+    }
+
+    /// Creates a new render pipeline builder from a WGSL file that is loaded at runtime.
+    ///
+    /// The pipeline will watch the file and rebuild itself with the same bindings whenever it changes.
+    /// If the new source fails to compile, the previous pipeline is kept and the error can be
+    /// read with [`RenderPipeline::reload_error`].
+    pub fn render_pipeline_file(&self, path: impl AsRef<Path>) -> RenderPipelineBuilder {
+        self.render_pipeline_file_with(path, Preprocessor::new())
+    }
+
+    /// Like [`Gpu::render_pipeline_file`], but runs the file through the given
+    /// preprocessor, for its search paths and defines. Included files are watched too.
+    pub fn render_pipeline_file_with(
+        &self,
+        path: impl AsRef<Path>,
+        preprocessor: Preprocessor,
+    ) -> RenderPipelineBuilder {
+        let mut file = ShaderFile::new(path.as_ref(), preprocessor);
+        self.render_pipeline_inner(file.load(self), Some(file))
+    }
 
+    fn render_pipeline_inner(
+        &self,
+        module: Result<Shader>,
+        file: Option<ShaderFile>,
+    ) -> RenderPipelineBuilder {
         RenderPipelineBuilder {
             gpu: self.clone(),
             module,
+            file,
             vertex_layout: VERTEX_BUFFER_LAYOUT,
             instance_layout: None,
-            bind_group_layout: Vec::new(),
-            bind_group: Vec::new(),
+            vertex_entry: "vert".into(),
+            fragment_entry: "frag".into(),
+            constants: Vec::new(),
+            entries: Vec::new(),
+            group: 0,
+            push_constant_size: None,
 
             topology: PrimitiveTopology::TriangleList,
             depth_compare: CompareFunction::LessEqual,
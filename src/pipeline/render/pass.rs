@@ -1,5 +1,5 @@
 use wgpu::{
-    Color, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
+    Color, CommandEncoder, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
     RenderPassDepthStencilAttachment, RenderPassDescriptor, StoreOp,
 };
 
@@ -9,6 +9,7 @@ use crate::{
         Texture,
     },
     gpu::Gpu,
+    submission::Submission,
 };
 
 impl Gpu {
@@ -17,42 +18,56 @@ impl Gpu {
         texture: &Texture<Rgba8>,
         depth: &Texture<Depth>,
         callback: impl FnOnce(&mut RenderPass),
-    ) {
+    ) -> Submission {
         self.immediate_dispatch(|encoder| {
-            let manager = &self.binding_manager;
+            self.encode_render_pass(encoder, texture, depth, callback)
+        })
+    }
 
-            let view = manager.get_resource(texture.id);
-            let view = view.expect_texture_view();
+    /// Encodes a render pass that clears and draws into the textures.
+    pub(crate) fn encode_render_pass(
+        &self,
+        encoder: &mut CommandEncoder,
+        texture: &Texture<Rgba8>,
+        depth: &Texture<Depth>,
+        callback: impl FnOnce(&mut RenderPass),
+    ) {
+        // Drawing quads may create the default buffers, which can't happen while
+        // the attachments below keep the binding manager locked.
+        self.default_buffers();
+        let manager = &self.binding_manager;
 
-            let depth = manager.get_resource(depth.id);
-            let depth = depth.expect_texture_view();
+        let view = manager.get_resource(texture.id);
+        let view = view.expect_texture_view();
 
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(Color::BLACK),
-                        store: StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: depth,
-                    depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
-                        store: StoreOp::Store,
-                    }),
-                    stencil_ops: Some(Operations {
-                        load: LoadOp::Clear(0),
-                        store: StoreOp::Store,
-                    }),
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+        let depth = manager.get_resource(depth.id);
+        let depth = depth.expect_texture_view();
 
-            callback(&mut render_pass);
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: StoreOp::Store,
+                }),
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
         });
+
+        callback(&mut render_pass);
     }
 }
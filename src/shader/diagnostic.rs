@@ -0,0 +1,146 @@
+use std::fmt::{self, Display};
+
+#[cfg(feature = "interactive")]
+use egui::{RichText, Ui};
+use naga::{front::wgsl::ParseError, valid::ValidationError, Span, WithSpan};
+
+/// A shader compilation error, with the locations in the source it refers to.
+/// Its [`Display`] implementation pretty-prints the error with source snippets.
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+    pub message: String,
+    pub labels: Vec<DiagnosticLabel>,
+    pub notes: Vec<String>,
+}
+
+/// A message attached to a span of shader source.
+#[derive(Debug, Clone)]
+pub struct DiagnosticLabel {
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+/// A span of shader source.
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub file: String,
+    /// 1-based line number.
+    pub line: u32,
+    /// 1-based column, in bytes.
+    pub column: u32,
+    /// Length of the span, in bytes.
+    pub length: u32,
+    /// Text of the line the span starts on.
+    pub text: String,
+}
+
+impl ShaderDiagnostic {
+    pub(crate) fn from_parse_error(file: &str, source: &str, error: &ParseError) -> Self {
+        Self {
+            message: error.message().to_owned(),
+            labels: error
+                .labels()
+                .map(|(span, message)| DiagnosticLabel::new(file, source, span, message))
+                .collect(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_validation_error(
+        file: &str,
+        source: &str,
+        error: &WithSpan<ValidationError>,
+    ) -> Self {
+        let mut notes = Vec::new();
+        let mut cause: &dyn std::error::Error = error.as_inner();
+        while let Some(next) = cause.source() {
+            notes.push(next.to_string());
+            cause = next;
+        }
+
+        Self {
+            message: error.as_inner().to_string(),
+            labels: error
+                .spans()
+                .map(|(span, message)| DiagnosticLabel::new(file, source, *span, message))
+                .collect(),
+            notes,
+        }
+    }
+
+    /// The location of the first label, which is usually where the error occurred.
+    pub fn location(&self) -> Option<&SourceLocation> {
+        self.labels.first().map(|x| &x.location)
+    }
+
+    /// Shows the diagnostic in an egui UI, like an error overlay.
+    #[cfg(feature = "interactive")]
+    pub fn ui(&self, ui: &mut Ui) {
+        let color = ui.visuals().error_fg_color;
+        ui.label(RichText::new(self.to_string()).monospace().color(color));
+    }
+}
+
+impl DiagnosticLabel {
+    fn new(file: &str, source: &str, span: Span, message: &str) -> Self {
+        Self {
+            message: message.to_owned(),
+            location: SourceLocation::new(file, source, span),
+        }
+    }
+}
+
+impl SourceLocation {
+    fn new(file: &str, source: &str, span: Span) -> Self {
+        let location = span.location(source);
+        let text = source.lines().nth(location.line_number as usize - 1);
+
+        Self {
+            file: file.to_owned(),
+            line: location.line_number,
+            column: location.line_position,
+            length: location.length,
+            text: text.unwrap_or_default().to_owned(),
+        }
+    }
+}
+
+impl Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+
+        let gutter = self
+            .labels
+            .iter()
+            .map(|x| x.location.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        for DiagnosticLabel { message, location } in &self.labels {
+            let pad = " ".repeat(gutter);
+            let column = location.column.saturating_sub(1) as usize;
+            let length = (location.length as usize)
+                .clamp(1, location.text.len().saturating_sub(column).max(1));
+
+            writeln!(
+                f,
+                "{pad}--> {}:{}:{}",
+                location.file, location.line, location.column
+            )?;
+            writeln!(f, "{pad} |")?;
+            writeln!(f, "{:>gutter$} | {}", location.line, location.text)?;
+            writeln!(
+                f,
+                "{pad} | {}{} {message}",
+                " ".repeat(column),
+                "^".repeat(length)
+            )?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "{} = note: {note}", " ".repeat(gutter))?;
+        }
+
+        Ok(())
+    }
+}
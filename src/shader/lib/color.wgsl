@@ -0,0 +1,57 @@
+// Color space conversions and color maps.
+
+// Rotates the hue of an rgb color by `hue` radians.
+fn hueShift(color: vec3<f32>, hue: f32) -> vec3<f32> {
+    let k = vec3(0.57735, 0.57735, 0.57735);
+    let cosAngle = cos(hue);
+    return color * cosAngle
+        + cross(k, color) * sin(hue)
+        + k * dot(k, color) * (1.0 - cosAngle);
+}
+
+// Converts a color from hsv to rgb, with all components in [0, 1].
+fn hsvToRgb(hsv: vec3<f32>) -> vec3<f32> {
+    let k = vec3(1.0, 2.0 / 3.0, 1.0 / 3.0);
+    let p = abs(fract(hsv.xxx + k) * 6.0 - 3.0);
+    return hsv.z * mix(vec3(1.0), clamp(p - 1.0, vec3(0.0), vec3(1.0)), hsv.y);
+}
+
+// Converts a color from srgb to linear rgb.
+fn srgbToLinear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, color <= vec3(0.04045));
+}
+
+// Maps t in [0, 1] to the viridis color map.
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3(0.2777273, 0.0054073, 0.3340998);
+    let c1 = vec3(0.1050930, 1.4046135, 1.3845901);
+    let c2 = vec3(-0.3308618, 0.2148476, 0.0950952);
+    let c3 = vec3(-4.6342305, -5.7991009, -19.3324409);
+    let c4 = vec3(6.2282699, 14.1799334, 56.6905526);
+    let c5 = vec3(4.7763850, -13.7451454, -65.3530326);
+    let c6 = vec3(-5.4354559, 4.6458525, 26.3124353);
+
+    let x = clamp(t, 0.0, 1.0);
+    return c0 + x * (c1 + x * (c2 + x * (c3 + x * (c4 + x * (c5 + x * c6)))));
+}
+
+// Maps t in [0, 1] to the turbo color map.
+fn turbo(t: f32) -> vec3<f32> {
+    let r4 = vec4(0.13572138, 4.61539260, -42.66032258, 132.13108234);
+    let g4 = vec4(0.09140261, 2.19418839, 4.84296658, -14.18503333);
+    let b4 = vec4(0.10667330, 12.64194608, -60.58204836, 110.36276771);
+    let r2 = vec2(-152.94239396, 59.28637943);
+    let g2 = vec2(4.27729857, 2.82956604);
+    let b2 = vec2(-89.90310912, 27.34824973);
+
+    let x = clamp(t, 0.0, 1.0);
+    let v4 = vec4(1.0, x, x * x, x * x * x);
+    let v2 = v4.zw * v4.z;
+    return vec3(
+        dot(v4, r4) + dot(v2, r2),
+        dot(v4, g4) + dot(v2, g2),
+        dot(v4, b4) + dot(v2, b2)
+    );
+}
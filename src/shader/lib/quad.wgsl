@@ -0,0 +1,15 @@
+// Vertex stage for drawing quads with `RenderPipeline::draw_quad`.
+// Pass `in.uv` through to the fragment shader to get the position on the quad.
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+@vertex
+fn vert(
+    @location(0) pos: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+) -> VertexOutput {
+    return VertexOutput(pos, uv);
+}
@@ -0,0 +1,40 @@
+// Hash based pseudo random numbers.
+//
+// Keep a `var seed: u32` per invocation (for example, seeded from the global
+// invocation id and a frame counter) and pass a pointer to it to each function.
+
+// PCG hash, see https://www.jcgt.org/published/0009/03/02
+fn pcgHash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Combines multiple values into a single seed.
+fn seed3(value: vec3<u32>) -> u32 {
+    return pcgHash(value.x ^ pcgHash(value.y ^ pcgHash(value.z)));
+}
+
+// Returns a random u32 and advances the seed.
+fn randU32(seed: ptr<function, u32>) -> u32 {
+    *seed = pcgHash(*seed);
+    return *seed;
+}
+
+// Returns a random f32 in [0, 1) and advances the seed.
+fn randF32(seed: ptr<function, u32>) -> f32 {
+    return f32(randU32(seed) >> 8u) / 16777216.0;
+}
+
+// Returns a random point in the unit square and advances the seed.
+fn randVec2(seed: ptr<function, u32>) -> vec2<f32> {
+    return vec2(randF32(seed), randF32(seed));
+}
+
+// Returns a random unit vector and advances the seed.
+fn randDirection(seed: ptr<function, u32>) -> vec3<f32> {
+    let z = randF32(seed) * 2.0 - 1.0;
+    let angle = randF32(seed) * 6.28318530718;
+    let r = sqrt(1.0 - z * z);
+    return vec3(r * cos(angle), r * sin(angle), z);
+}
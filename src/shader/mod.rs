@@ -0,0 +1,198 @@
+//! Shader validation and diagnostics.
+//!
+//! WGSL shaders are parsed and validated with naga before being handed to wgpu,
+//! so errors can be reported as a [`ShaderDiagnostic`] instead of a panic.
+//! The parsed module is kept around to check pipeline bindings against the shader.
+
+use std::sync::Arc;
+
+use naga::{
+    front::wgsl,
+    valid::{Capabilities, ModuleInfo, ValidationFlags, Validator},
+    Module, ShaderStage,
+};
+use wgpu::{ShaderModule, ShaderModuleDescriptor, ShaderSource};
+
+use crate::{
+    error::{self, Error},
+    gpu::Gpu,
+};
+
+mod diagnostic;
+pub mod preprocess;
+pub(crate) mod reflect;
+pub use diagnostic::{DiagnosticLabel, ShaderDiagnostic, SourceLocation};
+pub use preprocess::{Preprocessor, ProcessedShader};
+pub use reflect::BindingError;
+use reflect::Reflection;
+
+/// The shader source a pipeline is created from.
+pub enum PipelineSource<'a> {
+    /// A wgpu shader module descriptor, like the output of [`wgpu::include_wgsl`].
+    Module(ShaderModuleDescriptor<'a>),
+    /// The output of the [`Preprocessor`], with diagnostics pointing to the original files.
+    Preprocessed(&'a ProcessedShader),
+    /// A shader that has already been compiled with [`Gpu::create_shader`].
+    Shader(&'a Shader),
+}
+
+/// A compiled shader module along with its reflection, if it was written in WGSL.
+///
+/// Creating a shader once and passing it to several pipelines, each with their own
+/// entry point, avoids parsing and compiling the same module more than once.
+/// Cloning a shader is cheap.
+#[derive(Clone)]
+pub struct Shader {
+    pub(crate) module: ShaderModule,
+    pub(crate) reflection: Option<Arc<Reflection>>,
+}
+
+impl Shader {
+    /// Returns the names of the shader's entry points, or an empty list if it wasn't written in WGSL.
+    pub fn entry_points(&self) -> Vec<&str> {
+        match &self.reflection {
+            Some(reflection) => reflection.entry_points().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Checks that the shader has an entry point with the given name for the given stage.
+    pub(crate) fn check_entry_point(&self, name: &str, stage: ShaderStage) -> error::Result<()> {
+        match &self.reflection {
+            Some(reflection) if !reflection.has_entry_point(name, stage) => {
+                Err(Error::Validation(format!(
+                    "The shader has no {} entry point named `{name}`",
+                    format!("{stage:?}").to_lowercase()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that every constant corresponds to an `override` declared in the shader.
+    pub(crate) fn check_constants(&self, constants: &[(String, f64)]) -> error::Result<()> {
+        let Some(reflection) = &self.reflection else {
+            return Ok(());
+        };
+
+        match constants
+            .iter()
+            .find(|(key, _)| !reflection.has_override(key))
+        {
+            Some((key, _)) => Err(Error::Validation(format!(
+                "The shader has no override constant named `{key}`"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the workgroup size of a compute entry point, or `None` if the shader isn't WGSL.
+    /// Errors if it can't be evaluated or is zero along any axis.
+    pub(crate) fn workgroup_size(
+        &self,
+        entry_point: &str,
+        constants: &[(String, f64)],
+    ) -> error::Result<Option<[u32; 3]>> {
+        let Some(reflection) = &self.reflection else {
+            return Ok(None);
+        };
+
+        let size = reflection
+            .workgroup_size(entry_point, constants)
+            .ok_or_else(|| {
+                Error::Validation(format!(
+                    "The workgroup size of `{entry_point}` can't be evaluated, \
+                     it may only use literals, constants and overrides"
+                ))
+            })?;
+        if size.contains(&0) {
+            return Err(Error::Validation(format!(
+                "The workgroup size of `{entry_point}` is {size:?}, which is zero along an axis"
+            )));
+        }
+
+        Ok(Some(size))
+    }
+}
+
+/// Parses and validates a WGSL shader. The file name is only used in diagnostics.
+pub fn validate_wgsl(file: &str, source: &str) -> Result<(), ShaderDiagnostic> {
+    parse_wgsl(file, source).map(|_| ())
+}
+
+pub(crate) fn parse_wgsl(
+    file: &str,
+    source: &str,
+) -> Result<(Module, ModuleInfo), ShaderDiagnostic> {
+    let module = wgsl::parse_str(source)
+        .map_err(|err| ShaderDiagnostic::from_parse_error(file, source, &err))?;
+
+    // Capabilities are checked by wgpu against the enabled features later on.
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| ShaderDiagnostic::from_validation_error(file, source, &err))?;
+
+    Ok((module, info))
+}
+
+/// Parses and validates a shader module descriptor if it contains WGSL source.
+fn reflect(descriptor: &ShaderModuleDescriptor) -> Result<Option<Reflection>, ShaderDiagnostic> {
+    match &descriptor.source {
+        ShaderSource::Wgsl(source) => {
+            let (module, info) = parse_wgsl(descriptor.label.unwrap_or("wgsl"), source)?;
+            Ok(Some(Reflection::new(module, info)))
+        }
+        _ => Ok(None),
+    }
+}
+
+impl Gpu {
+    /// Compiles a shader that can be shared between pipelines.
+    pub fn create_shader<'a>(&self, source: impl Into<PipelineSource<'a>>) -> Shader {
+        self.try_create_shader(source)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Validates and compiles a shader that can be shared between pipelines,
+    /// returning any errors instead of panicking.
+    pub fn try_create_shader<'a>(
+        &self,
+        source: impl Into<PipelineSource<'a>>,
+    ) -> error::Result<Shader> {
+        let (descriptor, reflection) = match source.into() {
+            PipelineSource::Shader(shader) => return Ok(shader.clone()),
+            PipelineSource::Module(descriptor) => {
+                let reflection = reflect(&descriptor)?;
+                (descriptor, reflection)
+            }
+            PipelineSource::Preprocessed(shader) => {
+                let (module, info) = shader.parse()?;
+                (shader.descriptor(), Some(Reflection::new(module, info)))
+            }
+        };
+
+        let module = self.error_scope(|| self.device.create_shader_module(descriptor))?;
+        Ok(Shader {
+            module,
+            reflection: reflection.map(Arc::new),
+        })
+    }
+}
+
+impl<'a> From<ShaderModuleDescriptor<'a>> for PipelineSource<'a> {
+    fn from(value: ShaderModuleDescriptor<'a>) -> Self {
+        PipelineSource::Module(value)
+    }
+}
+
+impl<'a> From<&'a ProcessedShader> for PipelineSource<'a> {
+    fn from(value: &'a ProcessedShader) -> Self {
+        PipelineSource::Preprocessed(value)
+    }
+}
+
+impl<'a> From<&'a Shader> for PipelineSource<'a> {
+    fn from(value: &'a Shader) -> Self {
+        PipelineSource::Shader(value)
+    }
+}
@@ -0,0 +1,614 @@
+//! A small preprocessor for WGSL, supporting includes, defines, and conditional compilation.
+//!
+//! ```wgsl
+//! #include "tufa/quad.wgsl"
+//! #define ITERATIONS 100
+//!
+//! #ifdef DEBUG
+//! const COLOR: vec3<f32> = vec3(1.0, 0.0, 1.0);
+//! #else
+//! const COLOR: vec3<f32> = vec3(1.0);
+//! #endif
+//! ```
+//!
+//! Includes are resolved from sources added with [`Preprocessor::source`], then relative to
+//! the including file, then from each search path. Every file is only included once.
+//! tufa's own library is always available under the `tufa/` prefix:
+//!
+//! - `tufa/quad.wgsl`: The `vert` stage used for [`crate::pipeline::render::RenderPipeline::draw_quad`].
+//! - `tufa/color.wgsl`: `hueShift`, `hsvToRgb`, `srgbToLinear` and the `viridis` and `turbo` color maps.
+//! - `tufa/random.wgsl`: PCG hash based random numbers.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use naga::{valid::ModuleInfo, Module};
+use wgpu::{ShaderModuleDescriptor, ShaderSource};
+
+use crate::error::{self, Error};
+
+use super::{parse_wgsl, DiagnosticLabel, ShaderDiagnostic, SourceLocation};
+
+const LIBRARY: &[(&str, &str)] = &[
+    ("tufa/quad.wgsl", include_str!("lib/quad.wgsl")),
+    ("tufa/color.wgsl", include_str!("lib/color.wgsl")),
+    ("tufa/random.wgsl", include_str!("lib/random.wgsl")),
+];
+
+#[derive(Clone)]
+pub struct Preprocessor {
+    search_paths: Vec<PathBuf>,
+    sources: HashMap<String, Cow<'static, str>>,
+    defines: HashMap<String, String>,
+}
+
+/// The output of the [`Preprocessor`]. Can be passed to [`crate::gpu::Gpu::compute_pipeline`]
+/// or [`crate::gpu::Gpu::render_pipeline`], in which case any shader diagnostics point to the
+/// original files.
+pub struct ProcessedShader {
+    name: String,
+    source: String,
+
+    files: Vec<(String, String)>,
+    /// Where each line in `source` came from.
+    lines: Vec<Line>,
+    /// The files read from disk, which a hot reloaded pipeline watches for changes.
+    dependencies: Vec<PathBuf>,
+}
+
+/// The origin of a line of processed source.
+struct Line {
+    file: usize,
+    line: u32,
+    substitutions: Vec<Substitution>,
+}
+
+/// A define replaced in a line, as byte ranges of the value in the processed
+/// line and of the name in the original one.
+struct Substitution {
+    processed: Range<u32>,
+    original: Range<u32>,
+}
+
+/// A file found by [`Preprocessor::resolve`].
+struct Include {
+    /// Key to deduplicate includes by.
+    key: String,
+    name: String,
+    source: String,
+    /// The directory to resolve the file's own includes from.
+    dir: Option<PathBuf>,
+    /// The path it was read from, if it isn't an embedded source.
+    path: Option<PathBuf>,
+}
+
+struct State<'a> {
+    preprocessor: &'a Preprocessor,
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+    out: ProcessedShader,
+}
+
+/// An `#ifdef` or `#ifndef` block.
+struct Conditional {
+    active: bool,
+    parent_active: bool,
+    seen_else: bool,
+    line: u32,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self {
+            search_paths: Vec::new(),
+            sources: LIBRARY
+                .iter()
+                .map(|(name, source)| (name.to_string(), Cow::Borrowed(*source)))
+                .collect(),
+            defines: HashMap::new(),
+        }
+    }
+
+    /// Adds a directory to search for included files.
+    pub fn search_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    /// Adds an embedded source that can be included by name, like `include_str!("common.wgsl")`.
+    pub fn source(mut self, name: impl Into<String>, source: impl Into<Cow<'static, str>>) -> Self {
+        self.sources.insert(name.into(), source.into());
+        self
+    }
+
+    /// Defines a name as if `#define NAME value` was at the top of the shader.
+    pub fn define(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.defines.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// Processes a WGSL source. The name is used to resolve relative includes
+    /// and in diagnostics.
+    pub fn process(&self, name: &str, source: &str) -> error::Result<ProcessedShader> {
+        let dir = Path::new(name).parent().map(Path::to_path_buf);
+        self.process_inner(name, source.to_owned(), dir)
+    }
+
+    /// Reads and processes a WGSL file.
+    pub fn process_file(&self, path: impl AsRef<Path>) -> error::Result<ProcessedShader> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|err| Error::Io(path.to_owned(), Arc::new(err)))?;
+        let dir = path.parent().map(Path::to_path_buf);
+        let mut shader = self.process_inner(&path.to_string_lossy(), source, dir)?;
+        shader.dependencies.insert(0, path.to_owned());
+        Ok(shader)
+    }
+
+    fn process_inner(
+        &self,
+        name: &str,
+        source: String,
+        dir: Option<PathBuf>,
+    ) -> error::Result<ProcessedShader> {
+        let mut state = State {
+            preprocessor: self,
+            defines: self.defines.clone(),
+            included: HashSet::new(),
+            out: ProcessedShader {
+                name: name.to_owned(),
+                source: String::new(),
+                files: Vec::new(),
+                lines: Vec::new(),
+                dependencies: Vec::new(),
+            },
+        };
+
+        state.included.insert(name.to_owned());
+        state.process(name.to_owned(), source, dir)?;
+        Ok(state.out)
+    }
+
+    /// Finds an included file, first in the embedded sources and then on disk.
+    fn resolve(&self, name: &str, dir: Option<&Path>) -> Option<Include> {
+        if let Some(source) = self.sources.get(name) {
+            return Some(Include {
+                key: name.to_owned(),
+                name: name.to_owned(),
+                source: source.to_string(),
+                dir: Path::new(name).parent().map(Path::to_path_buf),
+                path: None,
+            });
+        }
+
+        let relative = dir.map(|x| x.join(name));
+        relative
+            .into_iter()
+            .chain(self.search_paths.iter().map(|x| x.join(name)))
+            .find_map(|path| {
+                let source = fs::read_to_string(&path).ok()?;
+                let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                Some(Include {
+                    key: key.to_string_lossy().into_owned(),
+                    name: path.to_string_lossy().into_owned(),
+                    source,
+                    dir: path.parent().map(Path::to_path_buf),
+                    path: Some(path),
+                })
+            })
+    }
+}
+
+impl State<'_> {
+    fn process(
+        &mut self,
+        name: String,
+        source: String,
+        dir: Option<PathBuf>,
+    ) -> Result<(), ShaderDiagnostic> {
+        let file = self.out.files.len();
+        self.out.files.push((name, source.clone()));
+
+        let mut conditionals = Vec::<Conditional>::new();
+        for (line, text) in (1..).zip(source.lines()) {
+            let active = conditionals.last().is_none_or(|x| x.active);
+
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    let (text, substitutions) = self.substitute(text);
+                    self.out.push_line(file, line, &text, substitutions);
+                }
+                continue;
+            };
+
+            let (directive, args) = directive
+                .trim()
+                .split_once(char::is_whitespace)
+                .map(|(directive, args)| (directive, args.trim()))
+                .unwrap_or((directive.trim(), ""));
+
+            match directive {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains_key(args);
+                    conditionals.push(Conditional {
+                        active: active && (defined == (directive == "ifdef")),
+                        parent_active: active,
+                        seen_else: false,
+                        line,
+                    });
+                }
+                "else" => {
+                    let Some(conditional) = conditionals.last_mut() else {
+                        return Err(self.error(
+                            file,
+                            line,
+                            "`#else` without `#ifdef`",
+                            "unexpected `#else`",
+                        ));
+                    };
+                    if conditional.seen_else {
+                        return Err(self.error(file, line, "duplicate `#else`", "second `#else`"));
+                    }
+                    conditional.seen_else = true;
+                    conditional.active = conditional.parent_active && !conditional.active;
+                }
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(self.error(
+                            file,
+                            line,
+                            "`#endif` without `#ifdef`",
+                            "unexpected `#endif`",
+                        ));
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let (key, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    if key.is_empty() {
+                        return Err(self.error(
+                            file,
+                            line,
+                            "missing name in `#define`",
+                            "expected a name",
+                        ));
+                    }
+                    self.defines.insert(key.to_owned(), value.trim().to_owned());
+                }
+                "undef" => {
+                    self.defines.remove(args);
+                }
+                "include" => {
+                    let Some(include) = args.strip_prefix('"').and_then(|x| x.strip_suffix('"'))
+                    else {
+                        return Err(self.error(
+                            file,
+                            line,
+                            "invalid `#include`",
+                            "expected a quoted path, like `#include \"common.wgsl\"`",
+                        ));
+                    };
+
+                    let Some(found) = self.preprocessor.resolve(include, dir.as_deref()) else {
+                        return Err(self.error(
+                            file,
+                            line,
+                            &format!("could not find `{include}`"),
+                            "included here",
+                        ));
+                    };
+
+                    if self.included.insert(found.key) {
+                        self.out.dependencies.extend(found.path);
+                        self.process(found.name, found.source, found.dir)?;
+                    }
+                }
+                _ => {
+                    return Err(self.error(
+                        file,
+                        line,
+                        "unknown preprocessor directive",
+                        "unknown directive",
+                    ))
+                }
+            }
+        }
+
+        if let Some(conditional) = conditionals.last() {
+            return Err(self.error(
+                file,
+                conditional.line,
+                "unterminated conditional",
+                "missing `#endif` for this block",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces all defined identifiers in a line with their values.
+    fn substitute<'a>(&self, line: &'a str) -> (Cow<'a, str>, Vec<Substitution>) {
+        if self.defines.is_empty() {
+            return (Cow::Borrowed(line), Vec::new());
+        }
+
+        let (code, comment) = match line.find("//") {
+            Some(index) => line.split_at(index),
+            None => (line, ""),
+        };
+
+        let mut out = String::with_capacity(line.len());
+        let mut substitutions = Vec::new();
+        let mut rest = code;
+        while let Some(start) = rest.find(is_ident) {
+            let end = rest[start..]
+                .find(|x| !is_ident(x))
+                .map_or(rest.len(), |x| x + start);
+            let ident = &rest[start..end];
+
+            out.push_str(&rest[..start]);
+            match self.defines.get(ident) {
+                Some(value) if !ident.starts_with(|x: char| x.is_ascii_digit()) => {
+                    let original = line.len() - rest.len() + start;
+                    substitutions.push(Substitution {
+                        processed: out.len() as u32..(out.len() + value.len()) as u32,
+                        original: original as u32..(original + ident.len()) as u32,
+                    });
+                    out.push_str(value);
+                }
+                _ => out.push_str(ident),
+            }
+            rest = &rest[end..];
+        }
+
+        out.push_str(rest);
+        out.push_str(comment);
+        (Cow::Owned(out), substitutions)
+    }
+
+    fn error(&self, file: usize, line: u32, message: &str, label: &str) -> ShaderDiagnostic {
+        let (name, source) = &self.out.files[file];
+        let text = source.lines().nth(line as usize - 1).unwrap_or_default();
+        let column = text.len() - text.trim_start().len();
+
+        ShaderDiagnostic {
+            message: message.to_owned(),
+            labels: vec![DiagnosticLabel {
+                message: label.to_owned(),
+                location: SourceLocation {
+                    file: name.to_owned(),
+                    line,
+                    column: column as u32 + 1,
+                    length: text.trim().len() as u32,
+                    text: text.to_owned(),
+                },
+            }],
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl ProcessedShader {
+    /// The processed WGSL source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns a shader module descriptor for the processed source.
+    /// Diagnostics from shaders created this way will refer to the processed source.
+    pub fn descriptor(&self) -> ShaderModuleDescriptor<'_> {
+        ShaderModuleDescriptor {
+            label: Some(&self.name),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&self.source)),
+        }
+    }
+
+    /// Returns the file name and line number that a 1-based line of the processed source came from.
+    pub fn origin(&self, line: u32) -> Option<(&str, u32)> {
+        let origin = self.lines.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[origin.file].0, origin.line))
+    }
+
+    /// Parses and validates the processed source, with any diagnostic pointing
+    /// to the original files.
+    pub fn validate(&self) -> Result<(), ShaderDiagnostic> {
+        self.parse().map(|_| ())
+    }
+
+    pub(crate) fn parse(&self) -> Result<(Module, ModuleInfo), ShaderDiagnostic> {
+        parse_wgsl(&self.name, &self.source).map_err(|mut diagnostic| {
+            for label in diagnostic.labels.iter_mut() {
+                self.map_location(&mut label.location);
+            }
+            diagnostic
+        })
+    }
+
+    /// The files this shader was read from, starting with the root file if it
+    /// was processed with [`Preprocessor::process_file`].
+    pub(crate) fn dependencies(&self) -> &[PathBuf] {
+        &self.dependencies
+    }
+
+    fn map_location(&self, location: &mut SourceLocation) {
+        let Some(origin) = self.lines.get(location.line as usize - 1) else {
+            return;
+        };
+
+        let (name, source) = &self.files[origin.file];
+        location.file = name.to_owned();
+        location.line = origin.line;
+        location.text = source
+            .lines()
+            .nth(origin.line as usize - 1)
+            .unwrap_or_default()
+            .to_owned();
+
+        let start = location.column - 1;
+        let end = start + location.length;
+        let start = map_offset(&origin.substitutions, start, false);
+        let end = map_offset(&origin.substitutions, end, true);
+        location.column = start + 1;
+        location.length = end.saturating_sub(start);
+    }
+
+    fn push_line(&mut self, file: usize, line: u32, text: &str, substitutions: Vec<Substitution>) {
+        self.source.push_str(text);
+        self.source.push('\n');
+        self.lines.push(Line {
+            file,
+            line,
+            substitutions,
+        });
+    }
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Maps a byte offset in a processed line back to the original line. Offsets
+/// inside a substituted value map to the start or `end` of the define's name.
+fn map_offset(substitutions: &[Substitution], offset: u32, end: bool) -> u32 {
+    let mut shift = 0;
+    for substitution in substitutions {
+        if offset <= substitution.processed.start {
+            break;
+        }
+        if offset < substitution.processed.end {
+            return match end {
+                true => substitution.original.end,
+                false => substitution.original.start,
+            };
+        }
+        shift = substitution.original.end as i64 - substitution.processed.end as i64;
+    }
+    (offset as i64 + shift) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Preprocessor};
+
+    #[test]
+    fn includes_each_file_once() {
+        let preprocessor = Preprocessor::new()
+            .source("a.wgsl", "#include \"common.wgsl\"\nconst A = 1;")
+            .source("common.wgsl", "const COMMON = 0;");
+        let shader = preprocessor
+            .process(
+                "main.wgsl",
+                "#include \"a.wgsl\"\n#include \"common.wgsl\"\nconst MAIN = 2;",
+            )
+            .unwrap();
+
+        assert_eq!(
+            shader.source(),
+            "const COMMON = 0;\nconst A = 1;\nconst MAIN = 2;\n"
+        );
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#ifdef A
+#ifndef B
+const X = 1;
+#else
+const X = 2;
+#endif
+#else
+#ifdef B
+const X = 3;
+#endif
+const X = 4;
+#endif";
+
+        let process = |preprocessor: Preprocessor| {
+            preprocessor
+                .process("main.wgsl", source)
+                .unwrap()
+                .source()
+                .to_owned()
+        };
+
+        let a = Preprocessor::new().define("A", "");
+        assert_eq!(process(a.clone()), "const X = 1;\n");
+        assert_eq!(process(a.define("B", "")), "const X = 2;\n");
+        assert_eq!(
+            process(Preprocessor::new().define("B", "")),
+            "const X = 3;\nconst X = 4;\n"
+        );
+        assert_eq!(process(Preprocessor::new()), "const X = 4;\n");
+    }
+
+    #[test]
+    fn unterminated_conditional() {
+        let Err(Error::Shader(error)) =
+            Preprocessor::new().process("main.wgsl", "const X = 1;\n#ifdef A\nconst Y = 2;")
+        else {
+            panic!("expected a shader diagnostic");
+        };
+        let location = error.location().unwrap();
+        assert_eq!((location.file.as_str(), location.line), ("main.wgsl", 2));
+    }
+
+    #[test]
+    fn substitutes_whole_identifiers() {
+        let shader = Preprocessor::new()
+            .define("SIZE", 64)
+            .process(
+                "main.wgsl",
+                "#define SCALE 0.5\nconst X = SIZE * SCALE + SIZE_2; // SIZE",
+            )
+            .unwrap();
+
+        assert_eq!(shader.source(), "const X = 64 * 0.5 + SIZE_2; // SIZE\n");
+    }
+
+    #[test]
+    fn maps_lines_to_original_files() {
+        let shader = Preprocessor::new()
+            .source("common.wgsl", "// Common\nconst COMMON = 0;")
+            .process(
+                "main.wgsl",
+                "#include \"common.wgsl\"\n#define X 1\n\nconst MAIN = X;",
+            )
+            .unwrap();
+
+        assert_eq!(shader.origin(1), Some(("common.wgsl", 1)));
+        assert_eq!(shader.origin(2), Some(("common.wgsl", 2)));
+        assert_eq!(shader.origin(3), Some(("main.wgsl", 3)));
+        assert_eq!(shader.origin(4), Some(("main.wgsl", 4)));
+        assert_eq!(shader.origin(5), None);
+    }
+
+    #[test]
+    fn maps_columns_past_substitutions() {
+        let line = "fn f() -> f32 { let x = VALUE + VALUE; return missing; }";
+        let shader = Preprocessor::new()
+            .define("VALUE", "vec3<f32>(1.0).x")
+            .source("common.wgsl", "const COMMON = 0;")
+            .process("main.wgsl", &format!("#include \"common.wgsl\"\n{line}"))
+            .unwrap();
+
+        let error = shader.validate().unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!((location.file.as_str(), location.line), ("main.wgsl", 2));
+        assert_eq!(location.text, line);
+        assert_eq!(location.column as usize, line.find("missing").unwrap() + 1);
+        assert_eq!(location.length as usize, "missing".len());
+    }
+}
@@ -0,0 +1,671 @@
+//! Reflection of the resources a shader declares, used to check pipeline bindings
+//! before they are handed to wgpu.
+
+use std::fmt::{self, Display};
+
+use naga::{
+    common::wgsl::TypeContext, valid::ModuleInfo, AddressSpace, Expression, GlobalVariable, Handle,
+    ImageClass, ImageDimension, Literal, Module, ResourceBinding, ScalarKind, StorageAccess,
+    TypeInner,
+};
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType, ShaderStages,
+    StorageTextureAccess, TextureSampleType, TextureViewDimension,
+};
+
+use crate::{
+    bindings::{manager::BindingManager, BindableResource, BindableResourceId},
+    pipeline::BindingEntry,
+};
+
+/// A resource bound to a pipeline doesn't match the shader variable at its binding.
+#[derive(Debug, Clone)]
+pub struct BindingError {
+    /// Name of the WGSL variable.
+    pub variable: String,
+    pub group: u32,
+    pub binding: u32,
+    pub message: String,
+}
+
+/// The parsed module a shader was created from.
+pub(crate) struct Reflection {
+    module: Module,
+    info: ModuleInfo,
+}
+
+impl Reflection {
+    pub fn new(module: Module, info: ModuleInfo) -> Self {
+        Self { module, info }
+    }
+
+    pub fn entry_points(&self) -> impl Iterator<Item = &str> {
+        self.module.entry_points.iter().map(|x| x.name.as_str())
+    }
+
+    pub fn has_entry_point(&self, name: &str, stage: naga::ShaderStage) -> bool {
+        self.module
+            .entry_points
+            .iter()
+            .any(|x| x.name == name && x.stage == stage)
+    }
+
+    /// Checks if the shader declares an `override` constant with the given name or `@id`.
+    pub fn has_override(&self, key: &str) -> bool {
+        self.module.overrides.iter().any(|(_, x)| {
+            x.name.as_deref() == Some(key) || x.id.is_some_and(|id| id.to_string() == key)
+        })
+    }
+
+    /// Returns the workgroup size of a compute entry point, evaluating any
+    /// `override` constants it depends on.
+    pub fn workgroup_size(
+        &self,
+        entry_point: &str,
+        constants: &[(String, f64)],
+    ) -> Option<[u32; 3]> {
+        let entry_point = self
+            .module
+            .entry_points
+            .iter()
+            .find(|x| x.name == entry_point && x.stage == naga::ShaderStage::Compute)?;
+
+        let mut size = entry_point.workgroup_size;
+        let overrides = entry_point.workgroup_size_overrides.unwrap_or_default();
+        for (size, expression) in size.iter_mut().zip(overrides) {
+            if let Some(expression) = expression {
+                *size = self.evaluate(expression, constants)? as u32;
+            }
+        }
+
+        Some(size)
+    }
+
+    /// Evaluates a global expression made up of literals, constants, and overrides.
+    fn evaluate(&self, expression: Handle<Expression>, constants: &[(String, f64)]) -> Option<f64> {
+        match &self.module.global_expressions[expression] {
+            Expression::Literal(literal) => Some(match *literal {
+                Literal::F64(x) | Literal::AbstractFloat(x) => x,
+                Literal::F32(x) => x as f64,
+                Literal::F16(x) => f32::from(x) as f64,
+                Literal::U32(x) => x as f64,
+                Literal::I32(x) => x as f64,
+                Literal::U64(x) => x as f64,
+                Literal::I64(x) | Literal::AbstractInt(x) => x as f64,
+                Literal::Bool(x) => x as u8 as f64,
+            }),
+            Expression::Constant(constant) => {
+                self.evaluate(self.module.constants[*constant].init, constants)
+            }
+            Expression::Override(handle) => {
+                let item = &self.module.overrides[*handle];
+                let value = constants.iter().rev().find(|(key, _)| {
+                    item.name.as_deref() == Some(key.as_str())
+                        || item.id.is_some_and(|id| id.to_string() == *key)
+                });
+
+                match value {
+                    Some((_, value)) => Some(*value),
+                    None => self.evaluate(item.init?, constants),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the global variables used by any of the given entry points,
+    /// along with the shader stages that use them.
+    fn used_globals<'a>(
+        &'a self,
+        entry_points: &'a [&str],
+    ) -> impl Iterator<Item = (&'a GlobalVariable, ShaderStages)> + 'a {
+        let entry_points = self
+            .module
+            .entry_points
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| entry_points.contains(&x.name.as_str()))
+            .map(|(i, x)| (self.info.get_entry_point(i), stage(x.stage)))
+            .collect::<Vec<_>>();
+
+        self.module
+            .global_variables
+            .iter()
+            .map(move |(handle, global)| {
+                let stages = entry_points
+                    .iter()
+                    .filter(|(info, _)| !info[handle].is_empty())
+                    .fold(ShaderStages::NONE, |acc, (_, stage)| acc | *stage);
+                (global, stages)
+            })
+            .filter(|(_, stages)| !stages.is_empty())
+    }
+
+    /// Returns the resource variables used by any of the given entry points,
+    /// along with the shader stages that use them.
+    fn used_resources<'a>(
+        &'a self,
+        entry_points: &'a [&str],
+    ) -> impl Iterator<Item = (&'a GlobalVariable, ResourceBinding, ShaderStages)> + 'a {
+        self.used_globals(entry_points)
+            .filter_map(|(global, stages)| Some((global, global.binding?, stages)))
+    }
+
+    /// Returns the size of the push constants used by the given entry points,
+    /// along with the stages that use them.
+    pub fn push_constants(&self, entry_points: &[&str]) -> Option<(u32, ShaderStages)> {
+        self.used_globals(entry_points)
+            .filter(|(global, _)| global.space == AddressSpace::PushConstant)
+            .map(|(global, stages)| {
+                let size = self.module.types[global.ty]
+                    .inner
+                    .size(self.module.to_ctx());
+                (size, stages)
+            })
+            .reduce(|(a, a_stages), (b, b_stages)| (a.max(b), a_stages | b_stages))
+    }
+
+    /// Finds the binding of the resource variable with the given name, along
+    /// with the stages of the given entry points that use it.
+    pub fn find(
+        &self,
+        name: &str,
+        entry_points: &[&str],
+    ) -> Option<(ResourceBinding, ShaderStages)> {
+        let global = self
+            .module
+            .global_variables
+            .iter()
+            .map(|(_, x)| x)
+            .find(|x| x.name.as_deref() == Some(name))?;
+
+        let stages = self
+            .used_globals(entry_points)
+            .find(|(x, _)| x.name.as_deref() == Some(name))
+            .map(|(_, stages)| stages)
+            .unwrap_or(ShaderStages::NONE);
+        Some((global.binding?, stages))
+    }
+
+    /// Checks that every resource variable used by the entry points
+    /// is bound exactly once, to a resource that matches its declaration.
+    pub fn check_bindings(
+        &self,
+        binding_manager: &BindingManager,
+        entry_points: &[&str],
+        entries: &[BindingEntry],
+    ) -> Result<(), BindingError> {
+        for (global, binding, _) in self.used_resources(entry_points) {
+            let error = |message| BindingError {
+                variable: global.name.clone().unwrap_or_default(),
+                group: binding.group,
+                binding: binding.binding,
+                message,
+            };
+
+            let declaration = self.declaration(global);
+            let is_bound =
+                |x: &&BindingEntry| x.group == binding.group && x.layout.binding == binding.binding;
+            let mut bound = entries.iter().filter(is_bound);
+            let (Some(entry), None) = (bound.next(), bound.next()) else {
+                let problem = match entries.iter().any(|x| is_bound(&x)) {
+                    true => "more than one resource was bound to it",
+                    false => "nothing was bound to it",
+                };
+                return Err(error(format!("shader uses `{declaration}`, but {problem}")));
+            };
+
+            if !self.compatible(global, &entry.layout) {
+                return Err(error(format!(
+                    "shader declares `{declaration}`, but {} was bound",
+                    describe(&entry.layout.ty, entry.layout.count)
+                )));
+            }
+
+            let BindableResourceId::Buffer(id) = entry.resource else {
+                continue;
+            };
+
+            let required = self.module.types[global.ty]
+                .inner
+                .size(self.module.to_ctx()) as u64;
+            let size = match &*binding_manager.get_resource(id) {
+                BindableResource::Buffer(buffer) => buffer.size(),
+                _ => continue,
+            };
+
+            if size < required {
+                return Err(error(format!(
+                    "shader declares `{declaration}`, which needs {required} bytes, \
+                     but the bound buffer is only {size} bytes"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compatible(&self, global: &GlobalVariable, entry: &BindGroupLayoutEntry) -> bool {
+        let mut inner = &self.module.types[global.ty].inner;
+        if let TypeInner::BindingArray { base, .. } = inner {
+            if entry.count.is_none() {
+                return false;
+            }
+            inner = &self.module.types[*base].inner;
+        } else if entry.count.is_some() {
+            return false;
+        }
+
+        match (global.space, inner, entry.ty) {
+            (
+                AddressSpace::Uniform,
+                _,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    ..
+                },
+            ) => true,
+            (
+                AddressSpace::Storage { access },
+                _,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only },
+                    ..
+                },
+            ) => read_only != access.contains(StorageAccess::STORE),
+            (
+                AddressSpace::Handle,
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                },
+                ty,
+            ) => image_compatible(*dim, *arrayed, *class, ty),
+            (AddressSpace::Handle, TypeInner::Sampler { comparison }, BindingType::Sampler(ty)) => {
+                *comparison == (ty == SamplerBindingType::Comparison)
+            }
+            (
+                AddressSpace::Handle,
+                TypeInner::AccelerationStructure { .. },
+                BindingType::AccelerationStructure { .. },
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Formats a global the way it would be declared in WGSL.
+    fn declaration(&self, global: &GlobalVariable) -> String {
+        let space = match global.space {
+            AddressSpace::Uniform => "<uniform>",
+            AddressSpace::Storage { access } if access.contains(StorageAccess::STORE) => {
+                "<storage, read_write>"
+            }
+            AddressSpace::Storage { .. } => "<storage, read>",
+            _ => "",
+        };
+
+        format!(
+            "var{space} {}: {}",
+            global.name.as_deref().unwrap_or("_"),
+            self.module.to_ctx().type_to_string(global.ty)
+        )
+    }
+}
+
+fn stage(stage: naga::ShaderStage) -> ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+        naga::ShaderStage::Task => ShaderStages::TASK,
+        naga::ShaderStage::Mesh => ShaderStages::MESH,
+    }
+}
+
+fn image_compatible(
+    dim: ImageDimension,
+    arrayed: bool,
+    class: ImageClass,
+    ty: BindingType,
+) -> bool {
+    let (view_dimension, class_matches) = match (class, ty) {
+        (
+            ImageClass::Sampled { kind, multi },
+            BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            },
+        ) => {
+            let kind_matches = matches!(
+                (kind, sample_type),
+                (ScalarKind::Float, TextureSampleType::Float { .. })
+                    | (ScalarKind::Sint, TextureSampleType::Sint)
+                    | (ScalarKind::Uint, TextureSampleType::Uint)
+            );
+            (view_dimension, kind_matches && multi == multisampled)
+        }
+        (
+            ImageClass::Depth { multi },
+            BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            },
+        ) => (
+            view_dimension,
+            sample_type == TextureSampleType::Depth && multi == multisampled,
+        ),
+        (
+            ImageClass::Storage { access, .. },
+            BindingType::StorageTexture {
+                access: bound,
+                view_dimension,
+                ..
+            },
+        ) => {
+            let expected = match (
+                access.contains(StorageAccess::LOAD),
+                access.contains(StorageAccess::STORE),
+            ) {
+                (true, true) => StorageTextureAccess::ReadWrite,
+                (true, false) => StorageTextureAccess::ReadOnly,
+                _ => StorageTextureAccess::WriteOnly,
+            };
+            (view_dimension, expected == bound)
+        }
+        _ => return false,
+    };
+
+    let expected = match (dim, arrayed) {
+        (ImageDimension::D1, _) => TextureViewDimension::D1,
+        (ImageDimension::D2, false) => TextureViewDimension::D2,
+        (ImageDimension::D2, true) => TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+    };
+
+    class_matches && view_dimension == expected
+}
+
+/// Describes a bound resource in terms of tufa's types.
+fn describe(ty: &BindingType, count: Option<std::num::NonZeroU32>) -> String {
+    let resource = match ty {
+        BindingType::Buffer { ty, .. } => match ty {
+            BufferBindingType::Uniform => "a uniform buffer".into(),
+            BufferBindingType::Storage { read_only: true } => {
+                "an immutable storage buffer (`Immutable`)".into()
+            }
+            BufferBindingType::Storage { read_only: false } => {
+                "a mutable storage buffer (`Mutable`)".into()
+            }
+        },
+        BindingType::Sampler(SamplerBindingType::Comparison) => "a comparison sampler".into(),
+        BindingType::Sampler(_) => "a sampler".into(),
+        BindingType::Texture {
+            view_dimension,
+            multisampled,
+            ..
+        } => format!(
+            "a {}{} texture",
+            if *multisampled { "multisampled " } else { "" },
+            dimension(*view_dimension)
+        ),
+        BindingType::StorageTexture { view_dimension, .. } => {
+            format!("a {} storage texture", dimension(*view_dimension))
+        }
+        BindingType::AccelerationStructure { .. } => "an acceleration structure".into(),
+    };
+
+    match count {
+        Some(count) => format!("a collection of {count} ({resource})"),
+        None => resource,
+    }
+}
+
+fn dimension(dimension: TextureViewDimension) -> &'static str {
+    match dimension {
+        TextureViewDimension::D1 => "1D",
+        TextureViewDimension::D2 => "2D",
+        TextureViewDimension::D2Array => "2D array",
+        TextureViewDimension::Cube => "cube",
+        TextureViewDimension::CubeArray => "cube array",
+        TextureViewDimension::D3 => "3D",
+    }
+}
+
+impl Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Binding mismatch for `{}` (@group({}) @binding({})): {}",
+            self.variable, self.group, self.binding, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{
+        BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType, ShaderStages,
+        StorageTextureAccess, TextureFormat, TextureSampleType, TextureViewDimension,
+    };
+
+    use super::Reflection;
+    use crate::{
+        bindings::{manager::BindingManager, BindableResourceId},
+        misc::ids::BufferId,
+        pipeline::BindingEntry,
+        shader::parse_wgsl,
+    };
+
+    const SHADER: &str = "
+@group(0) @binding(0) var<uniform> params: vec4<f32>;
+@group(0) @binding(1) var<storage, read> input: array<f32>;
+@group(0) @binding(2) var<storage, read_write> output: array<f32>;
+@group(0) @binding(3) var colors: texture_2d<f32>;
+@group(0) @binding(4) var depths: texture_depth_2d;
+@group(0) @binding(5) var filtering: sampler;
+@group(0) @binding(6) var comparison: sampler_comparison;
+@group(0) @binding(7) var image: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(8) var unused: texture_2d<u32>;
+
+@compute @workgroup_size(1)
+fn main() {
+    let color = textureSampleLevel(colors, filtering, params.xy, 0.0);
+    let depth = textureSampleCompareLevel(depths, comparison, params.xy, 0.5);
+    textureStore(image, vec2<i32>(0), color * depth);
+    output[0] = input[0];
+}
+";
+
+    fn reflection() -> Reflection {
+        let (module, info) = parse_wgsl("test.wgsl", SHADER).unwrap();
+        Reflection::new(module, info)
+    }
+
+    fn entry(binding: u32, ty: BindingType) -> BindingEntry {
+        BindingEntry {
+            name: None,
+            group: 0,
+            layout: BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::COMPUTE,
+                ty,
+                count: None,
+            },
+            resource: BindableResourceId::Buffer(BufferId::new()),
+        }
+    }
+
+    fn buffer(ty: BufferBindingType) -> BindingType {
+        BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+
+    fn texture(
+        sample_type: TextureSampleType,
+        view_dimension: TextureViewDimension,
+    ) -> BindingType {
+        BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: false,
+        }
+    }
+
+    fn storage_texture(access: StorageTextureAccess) -> BindingType {
+        BindingType::StorageTexture {
+            access,
+            format: TextureFormat::Rgba8Unorm,
+            view_dimension: TextureViewDimension::D2,
+        }
+    }
+
+    /// Checks if a resource of the given type can be bound to the variable at `binding`.
+    fn compatible(binding: u32, ty: BindingType) -> bool {
+        let reflection = reflection();
+        let (_, global) = reflection
+            .module
+            .global_variables
+            .iter()
+            .find(|(_, x)| x.binding.as_ref().is_some_and(|x| x.binding == binding))
+            .unwrap();
+        reflection.compatible(global, &entry(binding, ty).layout)
+    }
+
+    const FLOAT: TextureSampleType = TextureSampleType::Float { filterable: true };
+
+    #[test]
+    fn buffer_address_spaces() {
+        let uniform = buffer(BufferBindingType::Uniform);
+        let read = buffer(BufferBindingType::Storage { read_only: true });
+        let read_write = buffer(BufferBindingType::Storage { read_only: false });
+
+        assert!(compatible(0, uniform));
+        assert!(!compatible(0, read));
+        assert!(!compatible(0, read_write));
+
+        assert!(compatible(1, read));
+        assert!(!compatible(1, read_write));
+        assert!(!compatible(1, uniform));
+
+        assert!(compatible(2, read_write));
+        assert!(!compatible(2, read));
+        assert!(!compatible(2, uniform));
+    }
+
+    #[test]
+    fn texture_kinds() {
+        assert!(compatible(3, texture(FLOAT, TextureViewDimension::D2)));
+        assert!(!compatible(
+            3,
+            texture(TextureSampleType::Uint, TextureViewDimension::D2)
+        ));
+        assert!(!compatible(
+            3,
+            texture(TextureSampleType::Depth, TextureViewDimension::D2)
+        ));
+        assert!(!compatible(
+            3,
+            texture(FLOAT, TextureViewDimension::D2Array)
+        ));
+        assert!(!compatible(3, texture(FLOAT, TextureViewDimension::D3)));
+        assert!(!compatible(3, buffer(BufferBindingType::Uniform)));
+
+        assert!(compatible(
+            4,
+            texture(TextureSampleType::Depth, TextureViewDimension::D2)
+        ));
+        assert!(!compatible(4, texture(FLOAT, TextureViewDimension::D2)));
+
+        assert!(compatible(
+            7,
+            storage_texture(StorageTextureAccess::WriteOnly)
+        ));
+        assert!(!compatible(
+            7,
+            storage_texture(StorageTextureAccess::ReadOnly)
+        ));
+        assert!(!compatible(
+            7,
+            storage_texture(StorageTextureAccess::ReadWrite)
+        ));
+        assert!(!compatible(7, texture(FLOAT, TextureViewDimension::D2)));
+    }
+
+    #[test]
+    fn sampler_kinds() {
+        let filtering = BindingType::Sampler(SamplerBindingType::Filtering);
+        let comparison = BindingType::Sampler(SamplerBindingType::Comparison);
+
+        assert!(compatible(5, filtering));
+        assert!(!compatible(5, comparison));
+        assert!(compatible(6, comparison));
+        assert!(!compatible(6, filtering));
+        assert!(!compatible(5, texture(FLOAT, TextureViewDimension::D2)));
+    }
+
+    #[test]
+    fn binding_arrays_need_a_count() {
+        let mut entry = entry(3, texture(FLOAT, TextureViewDimension::D2));
+        entry.layout.count = Some(4.try_into().unwrap());
+        let reflection = reflection();
+        let (_, global) = reflection.module.global_variables.iter().nth(3).unwrap();
+        assert!(!reflection.compatible(global, &entry.layout));
+    }
+
+    #[test]
+    fn reports_mismatched_variable() {
+        let entries = [entry(
+            0,
+            buffer(BufferBindingType::Storage { read_only: false }),
+        )];
+        let error = reflection()
+            .check_bindings(&BindingManager::new(), &["main"], &entries)
+            .unwrap_err();
+
+        assert_eq!(
+            (error.variable.as_str(), error.group, error.binding),
+            ("params", 0, 0)
+        );
+        assert_eq!(
+            error.message,
+            "shader declares `var<uniform> params: vec4<f32>`, \
+             but a mutable storage buffer (`Mutable`) was bound"
+        );
+    }
+
+    #[test]
+    fn reports_unbound_and_duplicate_bindings() {
+        let manager = BindingManager::new();
+        let error = reflection()
+            .check_bindings(&manager, &["main"], &[])
+            .unwrap_err();
+        assert!(error.message.ends_with("nothing was bound to it"));
+
+        let uniform = buffer(BufferBindingType::Uniform);
+        let entries = [entry(0, uniform), entry(0, uniform)];
+        let error = reflection()
+            .check_bindings(&manager, &["main"], &entries)
+            .unwrap_err();
+        assert!(error
+            .message
+            .ends_with("more than one resource was bound to it"));
+    }
+
+    #[test]
+    fn ignores_unused_variables() {
+        let reflection = reflection();
+        let used = reflection
+            .used_resources(&["main"])
+            .map(|(_, binding, _)| binding.binding)
+            .collect::<Vec<_>>();
+        assert_eq!(used, (0..8).collect::<Vec<_>>());
+    }
+}
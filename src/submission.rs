@@ -0,0 +1,109 @@
+//! Handles to work submitted to the GPU.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wgpu::{Device, MaintainBase, Queue, SubmissionIndex};
+
+use crate::{
+    gpu::Gpu,
+    misc::{poll_driver::PollDriver, signal::Signal},
+};
+
+/// A handle to a submission of GPU work, returned by immediate dispatches,
+/// [`Gpu::flush_dispatch_queue`] and [`Gpu::record`]. Waiting on it only blocks
+/// until this submission is done, not the whole queue.
+///
+/// Submissions can also be awaited from any executor. The device is then
+/// polled on a background thread until the work is done.
+///
+/// A submission doesn't keep the [`Gpu`] alive, so dropping one never blocks.
+pub struct Submission {
+    device: Device,
+    queue: Queue,
+    poll_driver: Arc<PollDriver>,
+    index: SubmissionIndex,
+    done: Signal<()>,
+    polling: bool,
+}
+
+impl Submission {
+    /// Creates a handle to the latest submission. Must be called right after submitting.
+    pub(crate) fn new(gpu: &Gpu, index: SubmissionIndex) -> Self {
+        let done = Signal::new();
+        let signal = done.clone();
+        gpu.queue.on_submitted_work_done(move || signal.set(()));
+
+        Self {
+            device: gpu.device.clone(),
+            queue: gpu.queue.clone(),
+            poll_driver: gpu.poll_driver.clone(),
+            index,
+            done,
+            polling: false,
+        }
+    }
+
+    /// Checks if the GPU has finished the submitted work, without blocking.
+    pub fn is_done(&self) -> bool {
+        if !self.done.is_set() {
+            let _ = self.device.poll(MaintainBase::Poll);
+        }
+        self.done.is_set()
+    }
+
+    /// Blocks until the GPU has finished the submitted work.
+    pub fn wait(&self) {
+        if self.done.is_set() {
+            return;
+        }
+
+        let index = self.index.clone();
+        self.device
+            .poll(MaintainBase::WaitForSubmissionIndex(index))
+            .unwrap();
+    }
+
+    /// Blocks until the GPU has finished the submitted work or the timeout
+    /// passes, returning whether the work is done.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.is_done() {
+                return true;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            thread::sleep((deadline - now).min(Duration::from_micros(100)));
+        }
+    }
+
+    /// Keeps the device polled on a background thread until the submission is done,
+    /// which also runs any callbacks registered before this was called.
+    pub(crate) fn poll_in_background(&self) {
+        let guard = self.poll_driver.request(&self.device);
+        self.queue.on_submitted_work_done(move || drop(guard));
+    }
+}
+
+impl Future for Submission {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if !self.polling {
+            self.polling = true;
+            self.poll_in_background();
+        }
+
+        self.done.poll_set(cx)
+    }
+}